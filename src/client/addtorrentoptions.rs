@@ -0,0 +1,187 @@
+//! Contains the AddTorrentOptions builder.
+use std::ffi::CString;
+use std::mem;
+use std::path::PathBuf;
+
+use transmission_sys;
+
+use crate::error::{Error, TrResult};
+use crate::torrent::Priority;
+
+/// Options for adding a torrent to a `Client` made using a builder pattern.
+///
+/// Anything not set uses the session's defaults.
+///
+/// ```
+/// use transmission::client::AddTorrentOptions;
+/// use transmission::torrent::Priority;
+///
+/// let options = AddTorrentOptions::new()
+///     .paused(true)
+///     .download_dir("/tmp")
+///     .bandwidth_priority(Priority::High)
+///     .unwanted_files(vec![1, 2]);
+/// ```
+#[derive(Default)]
+pub struct AddTorrentOptions {
+    /// Add the torrent without starting it
+    paused: Option<bool>,
+    /// The directory to download the torrent to
+    download_dir: Option<PathBuf>,
+    /// Bandwidth priority of the torrent
+    bandwidth_priority: Option<Priority>,
+    /// Maximum number of peers of the torrent
+    peer_limit: Option<u16>,
+    /// Indices of files to download
+    wanted_files: Vec<u32>,
+    /// Indices of files not to download
+    unwanted_files: Vec<u32>,
+    /// Priorities of files by index
+    file_priorities: Vec<(u32, Priority)>,
+    /// Delete the .torrent file once the torrent is added
+    delete_source: Option<bool>,
+}
+
+impl AddTorrentOptions {
+    /// Create a new AddTorrentOptions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the torrent without starting it
+    pub fn paused(mut self, paused: bool) -> Self {
+        self.paused = Some(paused);
+        self
+    }
+
+    /// Set the directory the torrent downloads to
+    pub fn download_dir(mut self, dir: &str) -> Self {
+        self.download_dir = Some(PathBuf::from(dir));
+        self
+    }
+
+    /// Set the bandwidth priority of the torrent
+    pub fn bandwidth_priority(mut self, priority: Priority) -> Self {
+        self.bandwidth_priority = Some(priority);
+        self
+    }
+
+    /// Set the maximum number of peers of the torrent
+    pub fn peer_limit(mut self, limit: u16) -> Self {
+        self.peer_limit = Some(limit);
+        self
+    }
+
+    /// Download the files with these indices
+    ///
+    /// Indices that are not one of the torrent's files are skipped.
+    pub fn wanted_files(mut self, files: Vec<u32>) -> Self {
+        self.wanted_files.extend(files);
+        self
+    }
+
+    /// Don't download the files with these indices
+    ///
+    /// Indices that are not one of the torrent's files are skipped.
+    pub fn unwanted_files(mut self, files: Vec<u32>) -> Self {
+        self.unwanted_files.extend(files);
+        self
+    }
+
+    /// Set the priority of the files with these indices
+    ///
+    /// Adding the torrent fails with `Error::FileIndexInvalid` if an index is not
+    /// one of the torrent's files. A magnet link has no files until its metadata
+    /// is downloaded, so file priorities can't be given when adding one.
+    pub fn file_priorities(mut self, files: Vec<u32>, priority: Priority) -> Self {
+        self.file_priorities
+            .extend(files.into_iter().map(|f| (f, priority)));
+        self
+    }
+
+    /// Delete the .torrent file once the torrent is added. Only applies to torrent files.
+    pub fn delete_source(mut self, delete: bool) -> Self {
+        self.delete_source = Some(delete);
+        self
+    }
+
+    /// Apply the options to a torrent constructor
+    pub(crate) fn apply(&self, ctor: *mut transmission_sys::tr_ctor) -> TrResult<()> {
+        let force = transmission_sys::tr_ctorMode::TR_FORCE;
+        unsafe {
+            // Transmission writes the priorities without checking the indices
+            if !self.file_priorities.is_empty() {
+                let count = file_count(ctor)?;
+                if self.file_priorities.iter().any(|(f, _)| *f >= count) {
+                    return Err(Error::FileIndexInvalid);
+                }
+            }
+            if let Some(paused) = self.paused {
+                transmission_sys::tr_ctorSetPaused(ctor, force, paused);
+            }
+            if let Some(dir) = &self.download_dir {
+                let dir = dir.to_str().ok_or(Error::PathNotUtf8)?;
+                let dir = CString::new(dir).map_err(|_| Error::StringNul)?;
+                // The constructor keeps its own copy of the directory
+                transmission_sys::tr_ctorSetDownloadDir(ctor, force, dir.as_ptr());
+            }
+            if let Some(priority) = self.bandwidth_priority {
+                transmission_sys::tr_ctorSetBandwidthPriority(ctor, priority as i8);
+            }
+            if let Some(limit) = self.peer_limit {
+                transmission_sys::tr_ctorSetPeerLimit(ctor, force, limit);
+            }
+            if !self.wanted_files.is_empty() {
+                transmission_sys::tr_ctorSetFilesWanted(
+                    ctor,
+                    self.wanted_files.as_ptr(),
+                    self.wanted_files.len() as u32,
+                    true,
+                );
+            }
+            if !self.unwanted_files.is_empty() {
+                transmission_sys::tr_ctorSetFilesWanted(
+                    ctor,
+                    self.unwanted_files.as_ptr(),
+                    self.unwanted_files.len() as u32,
+                    false,
+                );
+            }
+            // Each priority can only be set once so the files are grouped by it
+            for priority in &[Priority::Low, Priority::Normal, Priority::High] {
+                let files: Vec<u32> = self
+                    .file_priorities
+                    .iter()
+                    .filter(|(_, p)| p == priority)
+                    .map(|(f, _)| *f)
+                    .collect();
+                if !files.is_empty() {
+                    transmission_sys::tr_ctorSetFilePriorities(
+                        ctor,
+                        files.as_ptr(),
+                        files.len() as u32,
+                        *priority as i8,
+                    );
+                }
+            }
+            if let Some(delete) = self.delete_source {
+                transmission_sys::tr_ctorSetDeleteSource(ctor, delete);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The number of files of the torrent in a constructor, zero for a magnet link
+unsafe fn file_count(ctor: *mut transmission_sys::tr_ctor) -> TrResult<u32> {
+    let mut info: transmission_sys::tr_info = mem::zeroed();
+    match transmission_sys::tr_torrentParse(ctor, &mut info) {
+        transmission_sys::tr_parse_result::TR_PARSE_ERR => Err(Error::ParseErr),
+        // Duplicates are still parsed
+        _ => {
+            let count = info.fileCount;
+            transmission_sys::tr_metainfoFree(&mut info);
+            Ok(count)
+        }
+    }
+}
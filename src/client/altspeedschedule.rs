@@ -0,0 +1,95 @@
+//! Contains the AltSpeedSchedule struct.
+use chrono::prelude::{NaiveTime, Timelike, Weekday};
+use serde::{Deserialize, Serialize};
+
+/// When the alternative ("turtle mode") speed limits are turned on automatically.
+///
+/// The schedule only takes effect while it is enabled with
+/// `Client::set_alt_speed_schedule_enabled()` or `ClientConfig::alt_speed_schedule_enabled()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AltSpeedSchedule {
+    /// Days of the week the schedule applies to.
+    pub days: Vec<Weekday>,
+    /// Time of day the alternative limits are turned on.
+    pub begin: NaiveTime,
+    /// Time of day the alternative limits are turned off.
+    pub end: NaiveTime,
+}
+
+impl AltSpeedSchedule {
+    /// Create a new schedule from the days and times it applies to
+    pub fn new(days: Vec<Weekday>, begin: NaiveTime, end: NaiveTime) -> Self {
+        Self { days, begin, end }
+    }
+
+    /// Rebuild a schedule from the values Transmission stores
+    pub(crate) fn from_raw(day_mask: i32, begin_minutes: i32, end_minutes: i32) -> Self {
+        let days = [
+            Weekday::Sun,
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+            Weekday::Sat,
+        ]
+        .iter()
+        .filter(|d| day_mask & day_bit(**d) != 0)
+        .cloned()
+        .collect();
+        Self {
+            days,
+            begin: time_from_minutes(begin_minutes),
+            end: time_from_minutes(end_minutes),
+        }
+    }
+
+    /// The days as a `tr_sched_day` bit mask
+    pub(crate) fn day_mask(&self) -> i32 {
+        self.days.iter().fold(0, |mask, d| mask | day_bit(*d))
+    }
+
+    /// The begin time in minutes since midnight
+    pub(crate) fn begin_minutes(&self) -> i32 {
+        (self.begin.hour() * 60 + self.begin.minute()) as i32
+    }
+
+    /// The end time in minutes since midnight
+    pub(crate) fn end_minutes(&self) -> i32 {
+        (self.end.hour() * 60 + self.end.minute()) as i32
+    }
+}
+
+/// The `tr_sched_day` bit of a day
+fn day_bit(day: Weekday) -> i32 {
+    1 << day.num_days_from_sunday()
+}
+
+fn time_from_minutes(minutes: i32) -> NaiveTime {
+    let minutes = minutes.max(0) as u32 % (24 * 60);
+    NaiveTime::from_hms(minutes / 60, minutes % 60, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_round_trip() {
+        let schedule = AltSpeedSchedule::new(
+            vec![Weekday::Mon, Weekday::Wed, Weekday::Sat],
+            NaiveTime::from_hms(9, 30, 0),
+            NaiveTime::from_hms(17, 0, 0),
+        );
+        assert_eq!(schedule.day_mask(), 0b100_1010);
+        assert_eq!(schedule.begin_minutes(), 570);
+        assert_eq!(schedule.end_minutes(), 1020);
+
+        let raw = AltSpeedSchedule::from_raw(
+            schedule.day_mask(),
+            schedule.begin_minutes(),
+            schedule.end_minutes(),
+        );
+        assert_eq!(raw, schedule);
+    }
+}
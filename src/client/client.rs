@@ -1,22 +1,51 @@
 //! Client for download management.
+use std::convert::TryFrom;
 use std::ffi;
 use std::fs::canonicalize;
-use std::ptr::NonNull;
+use std::mem;
+use std::os::raw::{c_int, c_void};
+use std::path::PathBuf;
+use std::ptr::{null_mut, NonNull};
+use std::slice;
 use std::sync::{Arc, RwLock};
 use transmission_sys;
 
-use super::ClientConfig;
+use super::clientconfig::existing_dir;
+use super::sessionevents::EventBus;
+use super::{AddTorrentOptions, AltSpeedSchedule, ClientConfig, ClientConfigPatch};
+use super::{SessionStats, Subscription};
 use crate::error::{Error, TrResult};
-use crate::torrent::Torrent;
+use crate::torrent::{forget_events, hash_from_hex, AddOutcome, MagnetLink, Torrent};
+
+// `tr_sessionGetTorrents` lives in libtransmission's private `session.h` so
+// transmission-sys does not generate a binding for it, but it is still exported.
+//
+// The alt speed day functions are re-declared to take the `tr_sched_day` bit mask
+// as a plain integer, since combinations of days are not variants of the Rust enum.
+extern "C" {
+    fn tr_sessionGetTorrents(
+        session: *mut transmission_sys::tr_session,
+        setme_n: *mut i32,
+    ) -> *mut *mut transmission_sys::tr_torrent;
+    #[link_name = "tr_sessionSetAltSpeedDay"]
+    pub(super) fn tr_sessionSetAltSpeedDayMask(
+        session: *mut transmission_sys::tr_session,
+        day: i32,
+    );
+    #[link_name = "tr_sessionGetAltSpeedDay"]
+    fn tr_sessionGetAltSpeedDayMask(session: *const transmission_sys::tr_session) -> i32;
+}
 
 /// Interface into the major functions of Transmission
 /// including adding, and removing torrents.
 ///
-/// The `Client` does not keep track of the created torrents itself.
+/// The `Client` does not keep track of the created torrents itself,
+/// but the session can be asked for them with `Client::torrents()`.
 ///
 /// Example of creating a session and adding a torrent and waiting for it to complete.
 /// ```no_run
 /// use transmission::{ ClientConfig, Client};
+/// use transmission::torrent::Completeness;
 ///
 /// # let test_dir = "/tmp/tr-test-long";
 /// # let config_dir = test_dir;
@@ -31,13 +60,18 @@ use crate::torrent::Torrent;
 ///    .download_dir(download_dir);
 /// let mut c = Client::new(c);
 ///
-/// let t = c.add_torrent_file(file_path).unwrap();
+/// let t = c.add_torrent_file(file_path).unwrap().into_torrent();
+///
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// t.on_complete(move |completeness| {
+///     if completeness == Completeness::Seed {
+///         let _ = tx.send(());
+///     }
+/// });
 /// t.start();
 ///
-/// // Run until done
-/// while t.stats().percent_complete < 1.0 {
-///     print!("{:#?}\r", t.stats().percent_complete);
-/// }
+/// // Wait until done
+/// rx.recv().unwrap();
 /// c.close();
 ///
 /// # std::fs::remove_dir_all(test_dir).unwrap();
@@ -45,39 +79,63 @@ use crate::torrent::Torrent;
 #[derive(Clone)]
 pub struct Client {
     tr_session: Arc<RwLock<NonNull<transmission_sys::tr_session>>>,
+    events: Arc<EventBus>,
 }
 
 impl Client {
     /// Creates a new `Client` and initializes the session.
     ///
     /// Takes a `ClientConfig` with the populated options.
+    ///
+    /// Panics if the session can't be created, see `Client::try_new()`.
     pub fn new(config: ClientConfig) -> Self {
+        Self::try_new(config).expect("Could not create the Client")
+    }
+
+    /// Creates a new `Client` and initializes the session.
+    ///
+    /// Fails with `Error::AppNameMissing`, `Error::ConfigDirMissing` or
+    /// `Error::DownloadDirMissing` if those are not set, `Error::DirNotFound` if the
+    /// download directory does not exist, `Error::PathNotUtf8` if a path can't be
+    /// given to Transmission, `Error::StringNul` if a string contains a NUL byte,
+    /// `Error::PortInvalid` if the RPC port is not a port number
+    /// and `Error::SessionInit` if Transmission fails.
+    pub fn try_new(mut config: ClientConfig) -> TrResult<Self> {
         // Change things into the types needed
-        let c_dir = config
-            .config_dir
-            .clone()
-            .expect("Configuration directory not set!");
-        let c_dir = ffi::CString::new(c_dir.to_str().unwrap()).unwrap();
+        let c_dir = config.config_dir.clone().ok_or(Error::ConfigDirMissing)?;
+        let c_dir = c_dir.to_str().ok_or(Error::PathNotUtf8)?;
+        let c_dir = ffi::CString::new(c_dir).map_err(|_| Error::PathNotUtf8)?;
+
+        let app_name = config.app_name.clone().ok_or(Error::AppNameMissing)?;
+        let app_name = ffi::CString::new(app_name).map_err(|_| Error::StringNul)?;
 
-        let app_name = config.app_name.clone().expect("Application name not set!");
-        let app_name = ffi::CString::new(app_name).unwrap();
+        // Checked again since the config may come from a file or be deserialized
+        let download_dir = config
+            .download_dir
+            .as_ref()
+            .ok_or(Error::DownloadDirMissing)?;
+        config.download_dir = Some(existing_dir(download_dir)?);
 
         let ses;
         unsafe {
-            let mut set = config.to_variant();
+            let mut set = config.to_variant()?;
             transmission_sys::tr_sessionLoadSettings(&mut set, c_dir.as_ptr(), app_name.as_ptr());
 
             ses = transmission_sys::tr_sessionInit(c_dir.as_ptr(), false, &mut set);
             transmission_sys::tr_variantFree(&mut set);
         }
-        Self {
-            tr_session: Arc::new(RwLock::new(NonNull::new(ses).unwrap())),
-        }
+        let ses = Arc::new(RwLock::new(NonNull::new(ses).ok_or(Error::SessionInit)?));
+        Ok(Self {
+            events: Arc::new(EventBus::new(&ses)),
+            tr_session: ses,
+        })
     }
 
     /// Adds a torrent using a torrent file.
     ///
     /// Takes the path to the torrent file on the disk.
+    /// If the session already has the torrent the existing one is returned
+    /// as `AddOutcome::Duplicate`, the same goes for the other ways of adding a torrent.
     ///
     /// ```
     /// use transmission::{ ClientConfig, Client};
@@ -95,25 +153,47 @@ impl Client {
     ///    .download_dir(download_dir);
     /// let mut c = Client::new(c);
     ///
-    /// let t = c.add_torrent_file(file_path).unwrap();
+    /// let t = c.add_torrent_file(file_path).unwrap().into_torrent();
     ///
     /// c.close();
     ///
     /// # std::fs::remove_dir_all(test_dir).unwrap();
     /// ```
-    pub fn add_torrent_file(&self, path: &str) -> TrResult<Torrent> {
-        let path = canonicalize(path).unwrap();
-        let path = ffi::CString::new(path.to_str().unwrap()).unwrap();
+    pub fn add_torrent_file(&self, path: &str) -> TrResult<AddOutcome> {
+        self.add_torrent_file_with(path, &AddTorrentOptions::new())
+    }
 
-        let mut ses = self.tr_session.write().unwrap();
-        let ctor;
-        unsafe {
-            ctor = transmission_sys::tr_ctorNew(ses.as_mut());
-        }
-        match unsafe { transmission_sys::tr_ctorSetMetainfoFromFile(ctor, path.as_ptr()) } {
-            0 => Torrent::from_ctor(ctor),
-            _ => Err(Error::Unknown),
-        }
+    /// Adds a torrent using a torrent file with the given `AddTorrentOptions`.
+    pub fn add_torrent_file_with(
+        &self,
+        path: &str,
+        options: &AddTorrentOptions,
+    ) -> TrResult<AddOutcome> {
+        let path = canonicalize(path).map_err(|_| Error::IOError)?;
+        let path = path.to_str().ok_or(Error::PathNotUtf8)?;
+        let path = ffi::CString::new(path).map_err(|_| Error::PathNotUtf8)?;
+
+        self.add_with_ctor(options, Error::Unknown, |ctor| unsafe {
+            transmission_sys::tr_ctorSetMetainfoFromFile(ctor, path.as_ptr())
+        })
+    }
+
+    /// Adds a torrent from the contents of a torrent file.
+    ///
+    /// Fails with `Error::Bencode` if the bytes are not valid bencode.
+    pub fn add_torrent_bytes(&self, metainfo: &[u8]) -> TrResult<AddOutcome> {
+        self.add_torrent_bytes_with(metainfo, &AddTorrentOptions::new())
+    }
+
+    /// Adds a torrent from the contents of a torrent file with the given `AddTorrentOptions`.
+    pub fn add_torrent_bytes_with(
+        &self,
+        metainfo: &[u8],
+        options: &AddTorrentOptions,
+    ) -> TrResult<AddOutcome> {
+        self.add_with_ctor(options, Error::Bencode, |ctor| unsafe {
+            transmission_sys::tr_ctorSetMetainfo(ctor, metainfo.as_ptr(), metainfo.len())
+        })
     }
 
     /// Adds a torrent using a magnet link.
@@ -136,24 +216,640 @@ impl Client {
     ///    .download_dir(download_dir);
     /// let mut c = Client::new(c);
     ///
-    /// let t = c.add_torrent_magnet(magnet_uri).unwrap();
+    /// let t = c.add_torrent_magnet(magnet_uri).unwrap().into_torrent();
+    ///
+    /// c.close();
+    ///
+    /// # std::fs::remove_dir_all(test_dir).unwrap();
+    pub fn add_torrent_magnet(&self, link: &str) -> TrResult<AddOutcome> {
+        self.add_torrent_magnet_with(link, &AddTorrentOptions::new())
+    }
+
+    /// Adds a torrent using a magnet link with the given `AddTorrentOptions`.
+    ///
+    /// Fails with `Error::MagnetInvalid` if the link can't be parsed.
+    pub fn add_torrent_magnet_with(
+        &self,
+        link: &str,
+        options: &AddTorrentOptions,
+    ) -> TrResult<AddOutcome> {
+        let link = ffi::CString::new(link).map_err(|_| Error::MagnetInvalid)?;
+        self.add_with_ctor(options, Error::MagnetInvalid, |ctor| unsafe {
+            transmission_sys::tr_ctorSetMetainfoFromMagnetLink(ctor, link.as_ptr())
+        })
+    }
+
+    /// Adds a torrent through a new constructor, which is freed afterwards
+    ///
+    /// `set_metainfo` gives the torrent to the constructor and returns non-zero
+    /// if it can't, in which case `error` is returned.
+    fn add_with_ctor<F>(
+        &self,
+        options: &AddTorrentOptions,
+        error: Error,
+        set_metainfo: F,
+    ) -> TrResult<AddOutcome>
+    where
+        F: FnOnce(*mut transmission_sys::tr_ctor) -> c_int,
+    {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            let ctor = transmission_sys::tr_ctorNew(ses.as_mut());
+            let outcome = match set_metainfo(ctor) {
+                0 => options.apply(ctor).and_then(|_| Torrent::from_ctor(ctor)),
+                _ => Err(error),
+            };
+            // The torrent keeps its own copy of everything it needs from the constructor
+            transmission_sys::tr_ctorFree(ctor);
+            if let Ok(AddOutcome::Added(torrent)) = &outcome {
+                self.events.track(torrent);
+            }
+            outcome
+        }
+    }
+
+    /// Add a torrent from a `MagnetLink`
+    ///
+    /// Transmission ignores the link's `length` and `select_only`, so all files
+    /// are downloaded. Use `Torrent::set_files_download_by_id()` once the
+    /// metadata is downloaded to only get the selected files.
+    pub fn add_magnet(&self, link: MagnetLink) -> TrResult<AddOutcome> {
+        self.add_torrent_magnet(&link.to_string())
+    }
+
+    /// Loads the torrents saved by a previous session in the configuration directory.
+    ///
+    /// Torrents that are already part of the session are skipped.
+    /// Returns the newly loaded torrents.
+    pub fn load_torrents(&self) -> Vec<Torrent> {
+        let mut ses = self.tr_session.write().unwrap();
+        let mut count = 0;
+        unsafe {
+            let ctor = transmission_sys::tr_ctorNew(ses.as_mut());
+            let tors = transmission_sys::tr_sessionLoadTorrents(ses.as_mut(), ctor, &mut count);
+            transmission_sys::tr_ctorFree(ctor);
+            let torrents = Self::collect_torrents(tors, count);
+            transmission_sys::tr_free(tors as *mut c_void);
+            torrents
+        }
+    }
+
+    /// Gets all the torrents in the session.
+    ///
+    /// This includes torrents added by other means than this `Client`,
+    /// such as those restored with `Client::load_torrents()`.
+    ///
+    /// ```
+    /// use transmission::{ ClientConfig, Client};
+    ///
+    /// # let test_dir = "/tmp/tr-test-torrents";
+    /// # let config_dir = test_dir;
+    /// # let download_dir = test_dir;
+    /// let file_path = "./alpine.torrent";
+    ///
+    /// # std::fs::create_dir(test_dir).unwrap();
+    ///
+    /// let c = ClientConfig::new()
+    ///    .app_name("testing")
+    ///    .config_dir(config_dir)
+    ///    .download_dir(download_dir);
+    /// let mut c = Client::new(c);
+    ///
+    /// let t = c.add_torrent_file(file_path).unwrap().into_torrent();
+    /// let torrents = c.torrents();
+    /// assert_eq!(torrents.len(), 1);
+    /// assert_eq!(torrents[0].id(), t.id());
     ///
     /// c.close();
     ///
     /// # std::fs::remove_dir_all(test_dir).unwrap();
-    pub fn add_torrent_magnet(&self, link: &str) -> TrResult<Torrent> {
-        let link = ffi::CString::new(link).unwrap();
+    /// ```
+    pub fn torrents(&self) -> Vec<Torrent> {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe { session_torrents(ses.as_mut()) }
+    }
+
+    /// Gets the torrent with the given ID, if it is in the session.
+    pub fn torrent_by_id(&self, id: i32) -> Option<Torrent> {
+        let mut ses = self.tr_session.write().unwrap();
+        let tor = unsafe { transmission_sys::tr_torrentFindFromId(ses.as_mut(), id) };
+        if tor.is_null() {
+            None
+        } else {
+            Torrent::from_tr_torrent(tor).ok()
+        }
+    }
+
+    /// Gets the torrent with the given info hash, if it is in the session.
+    ///
+    /// Takes the hash as a 40 character hex string like `TorrentInfo::hash_string`.
+    pub fn torrent_by_hash(&self, hash: &str) -> Option<Torrent> {
+        let hash = hash_from_hex(hash)?;
+        let mut ses = self.tr_session.write().unwrap();
+        let tor = unsafe { transmission_sys::tr_torrentFindFromHash(ses.as_mut(), hash.as_ptr()) };
+        if tor.is_null() {
+            None
+        } else {
+            Torrent::from_tr_torrent(tor).ok()
+        }
+    }
+
+    /// The stats of the current session
+    pub fn session_stats(&self) -> SessionStats {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            let mut stats: transmission_sys::tr_session_stats = mem::zeroed();
+            transmission_sys::tr_sessionGetStats(ses.as_ptr(), &mut stats);
+            Self::with_speeds(ses.as_ref(), stats)
+        }
+    }
+
+    /// The stats of all sessions combined, including the current one
+    pub fn cumulative_stats(&self) -> SessionStats {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            let mut stats: transmission_sys::tr_session_stats = mem::zeroed();
+            transmission_sys::tr_sessionGetCumulativeStats(ses.as_ptr(), &mut stats);
+            Self::with_speeds(ses.as_ref(), stats)
+        }
+    }
+
+    /// Resets both the current and cumulative stats to zero
+    pub fn reset_stats(&self) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionClearStats(ses.as_mut());
+        }
+    }
+
+    /// The settings the session is currently using
+    ///
+    /// Includes the changes made with `Client::apply()` and the other setters.
+    /// The application name is not part of the session's settings so it is not set.
+    pub fn config(&self) -> ClientConfig {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            let mut settings: transmission_sys::tr_variant = mem::zeroed();
+            transmission_sys::tr_variantInitDict(&mut settings, 0);
+            transmission_sys::tr_sessionGetSettings(ses.as_ptr(), &mut settings);
+            let mut config = ClientConfig::from_variant(&mut settings);
+            transmission_sys::tr_variantFree(&mut settings);
+
+            let config_dir =
+                ffi::CStr::from_ptr(transmission_sys::tr_sessionGetConfigDir(ses.as_ptr()));
+            config.config_dir = Some(PathBuf::from(config_dir.to_string_lossy().into_owned()));
+            config
+        }
+    }
+
+    /// Change the settings of the running session
+    ///
+    /// Only the options set in the patch are changed.
+    /// Fails without changing anything if a value can't be used, with `Error::DirNotFound`
+    /// if a directory does not exist, `Error::PathNotUtf8` if a path is not valid UTF-8
+    /// and `Error::StringNul` if a path or the blocklist URL contains a NUL byte.
+    ///
+    /// ```no_run
+    /// use transmission::client::{ClientConfigPatch, Encryption};
+    /// # use transmission::{Client, ClientConfig};
+    /// # let c = ClientConfig::new()
+    /// #    .app_name("testing")
+    /// #    .config_dir("/tmp/tr-test-apply")
+    /// #    .download_dir("/tmp/tr-test-apply");
+    /// # let client = Client::new(c);
+    ///
+    /// client
+    ///     .apply(
+    ///         ClientConfigPatch::new()
+    ///             .peer_port(51414)
+    ///             .encryption(Encryption::Required),
+    ///     )
+    ///     .unwrap();
+    /// assert!(client.config().to_settings_json().unwrap().contains("51414"));
+    /// ```
+    pub fn apply(&self, patch: ClientConfigPatch) -> TrResult<()> {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe { patch.apply(ses.as_mut()) }
+    }
+
+    /// Write the session's current settings to `settings.json` in the configuration directory
+    ///
+    /// Keys in the file that the session doesn't use, like the watch directory, are kept.
+    /// Fails with `Error::IOError` if the file can't be written.
+    pub fn save_settings(&self) -> TrResult<()> {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            let config_dir =
+                ffi::CStr::from_ptr(transmission_sys::tr_sessionGetConfigDir(ses.as_ptr()));
+            let path = PathBuf::from(config_dir.to_str().map_err(|_| Error::PathNotUtf8)?)
+                .join("settings.json");
+            let path = ffi::CString::new(path.to_str().ok_or(Error::PathNotUtf8)?)
+                .map_err(|_| Error::PathNotUtf8)?;
+            // Done like `tr_sessionSaveSettings()`, which doesn't report failures.
+            // The existing file is the base and the session's values override it.
+            let mut settings: transmission_sys::tr_variant = mem::zeroed();
+            transmission_sys::tr_variantInitDict(&mut settings, 0);
+            let mut file: transmission_sys::tr_variant = mem::zeroed();
+            if transmission_sys::tr_variantFromFile(
+                &mut file,
+                transmission_sys::tr_variant_fmt::TR_VARIANT_FMT_JSON,
+                path.as_ptr(),
+                null_mut(),
+            ) {
+                transmission_sys::tr_variantMergeDicts(&mut settings, &file);
+                transmission_sys::tr_variantFree(&mut file);
+            }
+            let mut current: transmission_sys::tr_variant = mem::zeroed();
+            transmission_sys::tr_variantInitDict(&mut current, 0);
+            transmission_sys::tr_sessionGetSettings(ses.as_mut(), &mut current);
+            transmission_sys::tr_variantMergeDicts(&mut settings, &current);
+            transmission_sys::tr_variantFree(&mut current);
+            let err = transmission_sys::tr_variantToFile(
+                &settings,
+                transmission_sys::tr_variant_fmt::TR_VARIANT_FMT_JSON,
+                path.as_ptr(),
+            );
+            transmission_sys::tr_variantFree(&mut settings);
+            if err == 0 {
+                Ok(())
+            } else {
+                Err(Error::IOError)
+            }
+        }
+    }
+
+    //# Speed limits, all in KB/s
+
+    /// The upload speed limit
+    pub fn speed_limit_up(&self) -> u32 {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionGetSpeedLimit_KBps(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+            )
+        }
+    }
+
+    /// Set the upload speed limit
+    ///
+    /// The limit is only used when enabled with `Client::set_speed_limit_up_enabled()`
+    pub fn set_speed_limit_up(&self, kbps: u32) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetSpeedLimit_KBps(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+                kbps,
+            );
+        }
+    }
+
+    /// Is the upload speed limited?
+    pub fn speed_limit_up_enabled(&self) -> bool {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionIsSpeedLimited(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+            )
+        }
+    }
+
+    /// Toggle limiting the upload speed
+    pub fn set_speed_limit_up_enabled(&self, enabled: bool) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionLimitSpeed(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+                enabled,
+            );
+        }
+    }
+
+    /// The download speed limit
+    pub fn speed_limit_down(&self) -> u32 {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionGetSpeedLimit_KBps(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+            )
+        }
+    }
+
+    /// Set the download speed limit
+    ///
+    /// The limit is only used when enabled with `Client::set_speed_limit_down_enabled()`
+    pub fn set_speed_limit_down(&self, kbps: u32) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetSpeedLimit_KBps(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+                kbps,
+            );
+        }
+    }
+
+    /// Is the download speed limited?
+    pub fn speed_limit_down_enabled(&self) -> bool {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionIsSpeedLimited(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+            )
+        }
+    }
+
+    /// Toggle limiting the download speed
+    pub fn set_speed_limit_down_enabled(&self, enabled: bool) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionLimitSpeed(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+                enabled,
+            );
+        }
+    }
+
+    /// The alternative upload speed limit
+    pub fn alt_speed_up(&self) -> u32 {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionGetAltSpeed_KBps(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+            )
+        }
+    }
+
+    /// Set the alternative upload speed limit
+    pub fn set_alt_speed_up(&self, kbps: u32) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetAltSpeed_KBps(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+                kbps,
+            );
+        }
+    }
+
+    /// The alternative download speed limit
+    pub fn alt_speed_down(&self) -> u32 {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionGetAltSpeed_KBps(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+            )
+        }
+    }
+
+    /// Set the alternative download speed limit
+    pub fn set_alt_speed_down(&self, kbps: u32) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetAltSpeed_KBps(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+                kbps,
+            );
+        }
+    }
+
+    /// Are the alternative speed limits ("turtle mode") in use?
+    pub fn alt_speed_enabled(&self) -> bool {
+        let ses = self.tr_session.read().unwrap();
+        unsafe { transmission_sys::tr_sessionUsesAltSpeed(ses.as_ptr()) }
+    }
+
+    /// Turn the alternative speed limits ("turtle mode") on or off
+    pub fn set_alt_speed_enabled(&self, enabled: bool) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionUseAltSpeed(ses.as_mut(), enabled);
+        }
+    }
+
+    /// The schedule for automatically turning on the alternative speed limits
+    pub fn alt_speed_schedule(&self) -> AltSpeedSchedule {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            AltSpeedSchedule::from_raw(
+                tr_sessionGetAltSpeedDayMask(ses.as_ptr()),
+                transmission_sys::tr_sessionGetAltSpeedBegin(ses.as_ptr()),
+                transmission_sys::tr_sessionGetAltSpeedEnd(ses.as_ptr()),
+            )
+        }
+    }
+
+    /// Set the schedule for automatically turning on the alternative speed limits
+    ///
+    /// The schedule is only used when enabled with `Client::set_alt_speed_schedule_enabled()`
+    pub fn set_alt_speed_schedule(&self, schedule: AltSpeedSchedule) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            tr_sessionSetAltSpeedDayMask(ses.as_mut(), schedule.day_mask());
+            transmission_sys::tr_sessionSetAltSpeedBegin(ses.as_mut(), schedule.begin_minutes());
+            transmission_sys::tr_sessionSetAltSpeedEnd(ses.as_mut(), schedule.end_minutes());
+        }
+    }
+
+    /// Is the alternative speed schedule in use?
+    pub fn alt_speed_schedule_enabled(&self) -> bool {
+        let ses = self.tr_session.read().unwrap();
+        unsafe { transmission_sys::tr_sessionUsesAltSpeedTime(ses.as_ptr()) }
+    }
+
+    /// Toggle using the alternative speed schedule
+    pub fn set_alt_speed_schedule_enabled(&self, enabled: bool) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionUseAltSpeedTime(ses.as_mut(), enabled);
+        }
+    }
+
+    /// How many torrents can download at once
+    pub fn download_queue_size(&self) -> u32 {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionGetQueueSize(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+            ) as u32
+        }
+    }
+
+    /// Set how many torrents can download at once
+    ///
+    /// The size is only used when enabled with `Client::set_download_queue_enabled()`
+    /// Values above `i32::MAX` are capped to it.
+    pub fn set_download_queue_size(&self, size: u32) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetQueueSize(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+                c_int::try_from(size).unwrap_or(c_int::MAX),
+            );
+        }
+    }
+
+    /// Is the number of downloading torrents limited?
+    pub fn download_queue_enabled(&self) -> bool {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionGetQueueEnabled(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+            )
+        }
+    }
+
+    /// Toggle limiting the number of downloading torrents
+    pub fn set_download_queue_enabled(&self, enabled: bool) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetQueueEnabled(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+                enabled,
+            );
+        }
+    }
+
+    /// How many torrents can seed at once
+    pub fn seed_queue_size(&self) -> u32 {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionGetQueueSize(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+            ) as u32
+        }
+    }
+
+    /// Set how many torrents can seed at once
+    ///
+    /// The size is only used when enabled with `Client::set_seed_queue_enabled()`
+    /// Values above `i32::MAX` are capped to it.
+    pub fn set_seed_queue_size(&self, size: u32) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetQueueSize(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+                c_int::try_from(size).unwrap_or(c_int::MAX),
+            );
+        }
+    }
+
+    /// Is the number of seeding torrents limited?
+    pub fn seed_queue_enabled(&self) -> bool {
+        let ses = self.tr_session.read().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionGetQueueEnabled(
+                ses.as_ptr(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+            )
+        }
+    }
+
+    /// Toggle limiting the number of seeding torrents
+    pub fn set_seed_queue_enabled(&self, enabled: bool) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetQueueEnabled(
+                ses.as_mut(),
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+                enabled,
+            );
+        }
+    }
+
+    /// Minutes without activity before a torrent is stalled
+    ///
+    /// Stalled torrents don't count against the queue sizes, see `TorrentStats::is_stalled`.
+    pub fn queue_stalled_minutes(&self) -> u32 {
+        let ses = self.tr_session.read().unwrap();
+        unsafe { transmission_sys::tr_sessionGetQueueStalledMinutes(ses.as_ptr()) as u32 }
+    }
+
+    /// Set the minutes without activity before a torrent is stalled
+    ///
+    /// Only used when enabled with `Client::set_queue_stalled_enabled()`
+    /// Values above `i32::MAX` are capped to it.
+    pub fn set_queue_stalled_minutes(&self, minutes: u32) {
         let mut ses = self.tr_session.write().unwrap();
-        let ctor;
         unsafe {
-            ctor = transmission_sys::tr_ctorNew(ses.as_mut());
+            transmission_sys::tr_sessionSetQueueStalledMinutes(
+                ses.as_mut(),
+                c_int::try_from(minutes).unwrap_or(c_int::MAX),
+            );
         }
-        match unsafe { transmission_sys::tr_ctorSetMetainfoFromMagnetLink(ctor, link.as_ptr()) } {
-            0 => Torrent::from_ctor(ctor),
-            _ => Err(Error::Unknown),
+    }
+
+    /// Can torrents become stalled?
+    pub fn queue_stalled_enabled(&self) -> bool {
+        let ses = self.tr_session.read().unwrap();
+        unsafe { transmission_sys::tr_sessionGetQueueStalledEnabled(ses.as_ptr()) }
+    }
+
+    /// Toggle marking inactive torrents as stalled
+    pub fn set_queue_stalled_enabled(&self, enabled: bool) {
+        let mut ses = self.tr_session.write().unwrap();
+        unsafe {
+            transmission_sys::tr_sessionSetQueueStalledEnabled(ses.as_mut(), enabled);
         }
     }
 
+    /// Adds the current session speeds to a set of stats
+    unsafe fn with_speeds(
+        ses: &transmission_sys::tr_session,
+        stats: transmission_sys::tr_session_stats,
+    ) -> SessionStats {
+        SessionStats::new(
+            stats,
+            transmission_sys::tr_sessionGetRawSpeed_KBps(
+                ses,
+                transmission_sys::tr_direction::TR_CLIENT_TO_PEER,
+            ),
+            transmission_sys::tr_sessionGetRawSpeed_KBps(
+                ses,
+                transmission_sys::tr_direction::TR_PEER_TO_CLIENT,
+            ),
+        )
+    }
+
+    /// Wraps an array of torrents given by Transmission
+    unsafe fn collect_torrents(
+        tors: *mut *mut transmission_sys::tr_torrent,
+        count: i32,
+    ) -> Vec<Torrent> {
+        if tors.is_null() {
+            return Vec::new();
+        }
+        slice::from_raw_parts(tors, count as usize)
+            .iter()
+            .filter_map(|t| Torrent::from_tr_torrent(*t).ok())
+            .collect()
+    }
+
+    /// Subscribe to the events of all torrents in the session
+    ///
+    /// Torrents are checked for changes about once a second, except for
+    /// completion, renames and moves which are sent as soon as they happen.
+    /// Torrents already in the session when a subscriber is added while there
+    /// are no others do not get an `Added` event.
+    pub fn subscribe(&self) -> Subscription {
+        self.events.subscribe()
+    }
+
     /// Consumes the Client and gracefully closes the session.
     ///
     /// This should always be called to ensure that the Client lasts as long as you intend.
@@ -163,22 +859,43 @@ impl Client {
 impl Drop for Client {
     fn drop(&mut self) {
         // If this is the last reference
-        if Arc::strong_count(&self.tr_session) == 1 {
+        if self.events.is_last(&self.tr_session) {
+            self.events.stop();
             // Close the session
             let ses = self.tr_session.write().unwrap();
             unsafe {
+                // Drop the torrents' event callbacks before the torrents are freed
+                let mut count = 0;
+                let tors = tr_sessionGetTorrents(ses.as_ptr(), &mut count);
+                if !tors.is_null() {
+                    for tor in slice::from_raw_parts(tors, count as usize) {
+                        forget_events(*tor);
+                    }
+                    transmission_sys::tr_free(tors as *mut c_void);
+                }
                 transmission_sys::tr_sessionClose(ses.as_ptr());
             }
         }
     }
 }
 
+/// Gets all the torrents in a session
+pub(super) unsafe fn session_torrents(ses: *mut transmission_sys::tr_session) -> Vec<Torrent> {
+    let mut count = 0;
+    let tors = tr_sessionGetTorrents(ses, &mut count);
+    let torrents = Client::collect_torrents(tors, count);
+    transmission_sys::tr_free(tors as *mut c_void);
+    torrents
+}
+
 unsafe impl std::marker::Send for Client {}
 unsafe impl std::marker::Sync for Client {}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::client::Encryption;
+    use crate::torrent::{Priority, TorrentState};
     use std::thread;
 
     #[test]
@@ -197,4 +914,191 @@ mod tests {
         thread::spawn(move || client.close());
         std::fs::remove_dir_all(test_dir).unwrap_or(());
     }
+
+    #[test]
+    fn add_bytes() {
+        let test_dir = "/tmp/tr-test-bytes";
+
+        std::fs::create_dir(test_dir).unwrap();
+
+        let c = ClientConfig::new()
+            .app_name("testing")
+            .config_dir(test_dir)
+            .download_dir(test_dir);
+        let client = Client::new(c);
+
+        let metainfo = std::fs::read("alpine.torrent").unwrap();
+        let added = client.add_torrent_bytes(&metainfo).unwrap();
+        assert!(!added.is_duplicate());
+        let duplicate = client.add_torrent_bytes(&metainfo).unwrap();
+        assert!(duplicate.is_duplicate());
+        assert_eq!(duplicate.into_torrent().id(), added.into_torrent().id());
+        assert_eq!(
+            client.add_torrent_bytes(b"not bencode").err(),
+            Some(Error::Bencode)
+        );
+
+        client.close();
+        std::fs::remove_dir_all(test_dir).unwrap_or(());
+    }
+
+    #[test]
+    fn add_with_options() {
+        let test_dir = "/tmp/tr-test-options";
+        let other_dir = "/tmp/tr-test-options/other";
+
+        std::fs::create_dir_all(other_dir).unwrap();
+
+        let c = ClientConfig::new()
+            .app_name("testing")
+            .config_dir(test_dir)
+            .download_dir(test_dir);
+        let client = Client::new(c);
+
+        let metainfo = std::fs::read("alpine.torrent").unwrap();
+        let options = AddTorrentOptions::new()
+            .paused(true)
+            .download_dir(other_dir)
+            .unwanted_files(vec![0])
+            .file_priorities(vec![0], Priority::High);
+        let torrent = client
+            .add_torrent_bytes_with(&metainfo, &options)
+            .unwrap()
+            .into_torrent();
+        assert_eq!(torrent.stats().state, TorrentState::Stopped);
+        assert_eq!(torrent.download_dir(), PathBuf::from(other_dir));
+        let files = torrent.file_stats();
+        assert!(!files[0].wanted);
+        assert_eq!(files[0].priority, Priority::High);
+
+        client.close();
+        std::fs::remove_dir_all(test_dir).unwrap_or(());
+    }
+
+    #[test]
+    fn add_invalid_file_index() {
+        let test_dir = "/tmp/tr-test-file-index";
+
+        std::fs::create_dir(test_dir).unwrap();
+
+        let c = ClientConfig::new()
+            .app_name("testing")
+            .config_dir(test_dir)
+            .download_dir(test_dir);
+        let client = Client::new(c);
+
+        // The torrent only has one file
+        let metainfo = std::fs::read("alpine.torrent").unwrap();
+        let options = AddTorrentOptions::new().file_priorities(vec![1], Priority::High);
+        assert_eq!(
+            client.add_torrent_bytes_with(&metainfo, &options).err(),
+            Some(Error::FileIndexInvalid)
+        );
+        // A magnet link has no files yet
+        let magnet = "magnet:?xt=urn:btih:f04905751c91af11a3745b1ce4500f4bf0de0d18";
+        let options = AddTorrentOptions::new().file_priorities(vec![0], Priority::High);
+        assert_eq!(
+            client.add_torrent_magnet_with(magnet, &options).err(),
+            Some(Error::FileIndexInvalid)
+        );
+        assert!(client.torrents().is_empty());
+
+        client.close();
+        std::fs::remove_dir_all(test_dir).unwrap_or(());
+    }
+
+    #[test]
+    fn invalid_magnet() {
+        let test_dir = "/tmp/tr-test-invalid-magnet";
+
+        std::fs::create_dir(test_dir).unwrap();
+
+        let c = ClientConfig::new()
+            .app_name("testing")
+            .config_dir(test_dir)
+            .download_dir(test_dir);
+        let client = Client::new(c);
+
+        assert_eq!(
+            client.add_torrent_magnet("not a magnet").err(),
+            Some(Error::MagnetInvalid)
+        );
+        assert_eq!(
+            client.add_torrent_magnet("magnet:?\0").err(),
+            Some(Error::MagnetInvalid)
+        );
+
+        client.close();
+        std::fs::remove_dir_all(test_dir).unwrap_or(());
+    }
+
+    #[test]
+    fn apply_config() {
+        let test_dir = "/tmp/tr-test-apply-config";
+
+        std::fs::create_dir(test_dir).unwrap();
+
+        let c = ClientConfig::new()
+            .app_name("testing")
+            .config_dir(test_dir)
+            .download_dir(test_dir);
+        let client = Client::new(c);
+
+        let before = client.config();
+        client
+            .apply(
+                ClientConfigPatch::new()
+                    .incomplete_dir(test_dir)
+                    .peer_port(51414)
+                    .encryption(Encryption::Required)
+                    .speed_limit_down(500)
+                    .speed_limit_down_enabled(true)
+                    .download_queue_size(3),
+            )
+            .unwrap();
+        // Only the patched options changed
+        let expected = before
+            .incomplete_dir(test_dir)
+            .peer_port(51414)
+            .encryption(Encryption::Required)
+            .speed_limit_down(500)
+            .speed_limit_down_enabled(true)
+            .download_queue_size(3);
+        assert_eq!(client.config(), expected);
+
+        client.close();
+        std::fs::remove_dir_all(test_dir).unwrap_or(());
+    }
+
+    #[test]
+    fn missing_app_name() {
+        let c = ClientConfig::new().config_dir("/tmp");
+        assert_eq!(Client::try_new(c).err(), Some(Error::AppNameMissing));
+    }
+
+    #[test]
+    fn invalid_app_name() {
+        let c = ClientConfig::new().app_name("test\0ing").config_dir("/tmp");
+        assert_eq!(Client::try_new(c).err(), Some(Error::StringNul));
+    }
+
+    #[test]
+    fn missing_download_dir() {
+        let c = ClientConfig::new().app_name("testing").config_dir("/tmp");
+        assert_eq!(Client::try_new(c).err(), Some(Error::DownloadDirMissing));
+    }
+
+    #[test]
+    fn removed_download_dir() {
+        let test_dir = "/tmp/tr-test-removed-dir";
+
+        std::fs::create_dir(test_dir).unwrap();
+        let c = ClientConfig::new()
+            .app_name("testing")
+            .config_dir("/tmp")
+            .download_dir(test_dir);
+        std::fs::remove_dir(test_dir).unwrap();
+
+        assert_eq!(Client::try_new(c).err(), Some(Error::DirNotFound));
+    }
 }
@@ -1,30 +1,261 @@
+use std::convert::TryFrom;
 use std::ffi::CString;
-use std::fs::canonicalize;
+use std::fs::{self, canonicalize};
+use std::io;
 use std::mem;
-use std::path::PathBuf;
+use std::os::raw::{c_char, c_void};
+use std::path::{Path, PathBuf};
+use std::ptr::{null, null_mut};
+use std::slice;
 
+use serde::{Deserialize, Serialize};
 use transmission_sys;
 
-// TODO expand on this to have all the options Transmission exposes
+use super::AltSpeedSchedule;
+use crate::error::{Error, TrResult};
+
+/// How peer connections are encrypted.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Encryption {
+    /// Prefer unencrypted connections but allow encrypted ones
+    Tolerated,
+    /// Prefer encrypted connections but allow unencrypted ones
+    Preferred,
+    /// Only allow encrypted connections
+    Required,
+}
+
+impl Encryption {
+    /// The mode for the value stored in the settings
+    fn from_raw(value: i64) -> Option<Self> {
+        [
+            Encryption::Tolerated,
+            Encryption::Preferred,
+            Encryption::Required,
+        ]
+        .iter()
+        .find(|e| transmission_sys::tr_encryption_mode::from(**e) as i64 == value)
+        .cloned()
+    }
+}
+
+impl From<transmission_sys::tr_encryption_mode> for Encryption {
+    fn from(mode: transmission_sys::tr_encryption_mode) -> Self {
+        match mode {
+            transmission_sys::tr_encryption_mode::TR_CLEAR_PREFERRED => Encryption::Tolerated,
+            transmission_sys::tr_encryption_mode::TR_ENCRYPTION_PREFERRED => Encryption::Preferred,
+            transmission_sys::tr_encryption_mode::TR_ENCRYPTION_REQUIRED => Encryption::Required,
+        }
+    }
+}
+
+impl From<Encryption> for transmission_sys::tr_encryption_mode {
+    fn from(mode: Encryption) -> Self {
+        match mode {
+            Encryption::Tolerated => transmission_sys::tr_encryption_mode::TR_CLEAR_PREFERRED,
+            Encryption::Preferred => transmission_sys::tr_encryption_mode::TR_ENCRYPTION_PREFERRED,
+            Encryption::Required => transmission_sys::tr_encryption_mode::TR_ENCRYPTION_REQUIRED,
+        }
+    }
+}
+
+/// How disk space is reserved for new files.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Preallocation {
+    /// Don't reserve space
+    None,
+    /// Create sparse files of the full size
+    Sparse,
+    /// Write out the full size of the files
+    Full,
+}
+
+impl Preallocation {
+    /// The mode for the value stored in the settings
+    fn from_raw(value: i64) -> Option<Self> {
+        [
+            Preallocation::None,
+            Preallocation::Sparse,
+            Preallocation::Full,
+        ]
+        .iter()
+        .find(|p| transmission_sys::tr_preallocation_mode::from(**p) as i64 == value)
+        .cloned()
+    }
+}
+
+impl From<transmission_sys::tr_preallocation_mode> for Preallocation {
+    fn from(mode: transmission_sys::tr_preallocation_mode) -> Self {
+        match mode {
+            transmission_sys::tr_preallocation_mode::TR_PREALLOCATE_NONE => Preallocation::None,
+            transmission_sys::tr_preallocation_mode::TR_PREALLOCATE_SPARSE => Preallocation::Sparse,
+            transmission_sys::tr_preallocation_mode::TR_PREALLOCATE_FULL => Preallocation::Full,
+        }
+    }
+}
+
+impl From<Preallocation> for transmission_sys::tr_preallocation_mode {
+    fn from(mode: Preallocation) -> Self {
+        match mode {
+            Preallocation::None => transmission_sys::tr_preallocation_mode::TR_PREALLOCATE_NONE,
+            Preallocation::Sparse => transmission_sys::tr_preallocation_mode::TR_PREALLOCATE_SPARSE,
+            Preallocation::Full => transmission_sys::tr_preallocation_mode::TR_PREALLOCATE_FULL,
+        }
+    }
+}
+
 /// Configuration for the torrent client made using a builder pattern.
-#[derive(Default)]
+///
+/// Every option maps to a key of Transmission's `settings.json`.
+/// Anything not set uses the value in `settings.json` or Transmission's default.
+///
+/// A config can also be loaded from and written as `settings.json` with
+/// `ClientConfig::from_settings_file()` and `ClientConfig::to_settings_json()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ClientConfig {
     /// The name of the client application
     pub(crate) app_name: Option<String>,
     /// The path to the configuration directory
     pub(crate) config_dir: Option<PathBuf>,
     /// The path to the download directory
-    download_dir: Option<PathBuf>,
+    pub(crate) download_dir: Option<PathBuf>,
+    /// The path to keep torrents in until they are complete
+    incomplete_dir: Option<PathBuf>,
+    /// Are incomplete torrents kept in the incomplete directory?
+    incomplete_dir_enabled: Option<bool>,
+    /// Add `.part` to the names of incomplete files
+    rename_partial_files: Option<bool>,
+    /// How disk space is reserved for new files
+    preallocation: Option<Preallocation>,
+    /// The umask used for new files
+    umask: Option<u32>,
+    /// Size of the disk cache in MB
+    cache_size_mb: Option<u32>,
+    /// Whether or not to read ahead pieces that will be uploaded
+    prefetch_enabled: Option<bool>,
+    /// Start torrents as soon as they are added
+    start_added_torrents: Option<bool>,
+    /// Delete .torrent files once they are added
+    trash_original_torrent_files: Option<bool>,
+    /// The directory to add .torrent files from
+    watch_dir: Option<PathBuf>,
+    /// Are .torrent files added from the watch directory?
+    watch_dir_enabled: Option<bool>,
+    /// The script to run when a torrent is done
+    script_torrent_done_filename: Option<PathBuf>,
+    /// Is the torrent done script run?
+    script_torrent_done_enabled: Option<bool>,
     /// Whether or not to use UTP
     use_utp: bool,
     /// What level of logging to use.
     log_level: i64,
+    /// The port to listen for peers on
+    peer_port: Option<u16>,
+    /// Pick a random peer port on start
+    peer_port_random_on_start: Option<bool>,
+    /// Lowest random peer port
+    peer_port_random_low: Option<u16>,
+    /// Highest random peer port
+    peer_port_random_high: Option<u16>,
+    /// Forward the peer port with UPnP or NAT-PMP
+    port_forwarding_enabled: Option<bool>,
+    /// IPv4 address to listen for peers on
+    bind_address_ipv4: Option<String>,
+    /// IPv6 address to listen for peers on
+    bind_address_ipv6: Option<String>,
+    /// Maximum number of peers over all torrents
+    peer_limit_global: Option<u16>,
+    /// Maximum number of peers per torrent
+    peer_limit_per_torrent: Option<u16>,
+    /// Maximum number of peers uploaded to per torrent
+    upload_slots_per_torrent: Option<u32>,
+    /// TCP congestion algorithm used for peers
+    peer_congestion_algorithm: Option<String>,
+    /// Type of service of peer connections
+    peer_socket_tos: Option<String>,
+    /// Hours before the peer ID is regenerated
+    peer_id_ttl_hours: Option<u32>,
+    /// How peer connections are encrypted
+    encryption: Option<Encryption>,
+    /// Whether or not to use DHT
+    dht_enabled: Option<bool>,
+    /// Whether or not to use peer exchange
+    pex_enabled: Option<bool>,
+    /// Whether or not to use local peer discovery
+    lpd_enabled: Option<bool>,
+    /// Scrape trackers of stopped torrents
+    scrape_paused_torrents_enabled: Option<bool>,
+    /// Is the blocklist used?
+    blocklist_enabled: Option<bool>,
+    /// The URL to update the blocklist from
+    blocklist_url: Option<String>,
     /// Is RPC enabled?
     rpc_enabled: bool,
     /// The URL the RPC will serve on
     rpc_url: Option<String>,
     /// The port the RPC will serve on
     rpc_port: Option<String>,
+    /// The address the RPC will serve on
+    rpc_bind_address: Option<String>,
+    /// Does the RPC require a username and password?
+    rpc_authentication_required: Option<bool>,
+    /// The RPC username
+    rpc_username: Option<String>,
+    /// The RPC password
+    rpc_password: Option<String>,
+    /// IP addresses allowed to use the RPC
+    rpc_whitelist: Option<Vec<String>>,
+    /// Is the RPC whitelist used?
+    rpc_whitelist_enabled: Option<bool>,
+    /// Host names allowed to use the RPC
+    rpc_host_whitelist: Option<Vec<String>>,
+    /// Is the RPC host whitelist used?
+    rpc_host_whitelist_enabled: Option<bool>,
+    /// Upload speed limit in KB/s
+    speed_limit_up: Option<u32>,
+    /// Is the upload speed limited?
+    speed_limit_up_enabled: Option<bool>,
+    /// Download speed limit in KB/s
+    speed_limit_down: Option<u32>,
+    /// Is the download speed limited?
+    speed_limit_down_enabled: Option<bool>,
+    /// Alternative upload speed limit in KB/s
+    alt_speed_up: Option<u32>,
+    /// Alternative download speed limit in KB/s
+    alt_speed_down: Option<u32>,
+    /// Are the alternative speed limits in use?
+    alt_speed_enabled: Option<bool>,
+    /// When to automatically use the alternative speed limits
+    alt_speed_schedule: Option<AltSpeedSchedule>,
+    /// Is the alternative speed schedule in use?
+    alt_speed_schedule_enabled: Option<bool>,
+    /// Default seed ratio limit
+    ratio_limit: Option<f64>,
+    /// Is the seed ratio limited by default?
+    ratio_limit_enabled: Option<bool>,
+    /// Default idle seeding limit in minutes
+    idle_seeding_limit: Option<u16>,
+    /// Is idle seeding limited by default?
+    idle_seeding_limit_enabled: Option<bool>,
+    /// How many torrents can download at once
+    download_queue_size: Option<u32>,
+    /// Is the number of downloading torrents limited?
+    download_queue_enabled: Option<bool>,
+    /// How many torrents can seed at once
+    seed_queue_size: Option<u32>,
+    /// Is the number of seeding torrents limited?
+    seed_queue_enabled: Option<bool>,
+    /// Minutes without activity before a torrent is stalled
+    queue_stalled_minutes: Option<u32>,
+    /// Can torrents become stalled?
+    queue_stalled_enabled: Option<bool>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ClientConfig {
@@ -34,11 +265,120 @@ impl ClientConfig {
             app_name: None,
             config_dir: None,
             download_dir: None,
+            incomplete_dir: None,
+            incomplete_dir_enabled: None,
+            rename_partial_files: None,
+            preallocation: None,
+            umask: None,
+            cache_size_mb: None,
+            prefetch_enabled: None,
+            start_added_torrents: None,
+            trash_original_torrent_files: None,
+            watch_dir: None,
+            watch_dir_enabled: None,
+            script_torrent_done_filename: None,
+            script_torrent_done_enabled: None,
             use_utp: true,
             log_level: 1,
+            peer_port: None,
+            peer_port_random_on_start: None,
+            peer_port_random_low: None,
+            peer_port_random_high: None,
+            port_forwarding_enabled: None,
+            bind_address_ipv4: None,
+            bind_address_ipv6: None,
+            peer_limit_global: None,
+            peer_limit_per_torrent: None,
+            upload_slots_per_torrent: None,
+            peer_congestion_algorithm: None,
+            peer_socket_tos: None,
+            peer_id_ttl_hours: None,
+            encryption: None,
+            dht_enabled: None,
+            pex_enabled: None,
+            lpd_enabled: None,
+            scrape_paused_torrents_enabled: None,
+            blocklist_enabled: None,
+            blocklist_url: None,
             rpc_enabled: false,
             rpc_url: None,
             rpc_port: None,
+            rpc_bind_address: None,
+            rpc_authentication_required: None,
+            rpc_username: None,
+            rpc_password: None,
+            rpc_whitelist: None,
+            rpc_whitelist_enabled: None,
+            rpc_host_whitelist: None,
+            rpc_host_whitelist_enabled: None,
+            speed_limit_up: None,
+            speed_limit_up_enabled: None,
+            speed_limit_down: None,
+            speed_limit_down_enabled: None,
+            alt_speed_up: None,
+            alt_speed_down: None,
+            alt_speed_enabled: None,
+            alt_speed_schedule: None,
+            alt_speed_schedule_enabled: None,
+            ratio_limit: None,
+            ratio_limit_enabled: None,
+            idle_seeding_limit: None,
+            idle_seeding_limit_enabled: None,
+            download_queue_size: None,
+            download_queue_enabled: None,
+            seed_queue_size: None,
+            seed_queue_enabled: None,
+            queue_stalled_minutes: None,
+            queue_stalled_enabled: None,
+        }
+    }
+
+    /// Load a config from a Transmission `settings.json` file
+    ///
+    /// Keys missing from the file are left unset.
+    /// The application name and configuration directory are not part of the file
+    /// and still have to be set.
+    pub fn from_settings_file(path: &str) -> TrResult<Self> {
+        let json = fs::read(path).map_err(|_| Error::IOError)?;
+        unsafe {
+            let mut variant: transmission_sys::tr_variant = mem::zeroed();
+            let err = transmission_sys::tr_variantFromBuf(
+                &mut variant,
+                transmission_sys::tr_variant_fmt::TR_VARIANT_FMT_JSON,
+                json.as_ptr() as *const c_void,
+                json.len(),
+                null(),
+                null_mut(),
+            );
+            if err != 0 {
+                return Err(Error::SettingsInvalid);
+            }
+            let config = Self::from_variant(&mut variant);
+            transmission_sys::tr_variantFree(&mut variant);
+            Ok(config)
+        }
+    }
+
+    /// The config as the contents of a Transmission `settings.json` file
+    ///
+    /// Only the options that are set are included.
+    /// Fails with `Error::StringNul` if a string contains a NUL byte,
+    /// `Error::PathNotUtf8` if a path is not valid UTF-8
+    /// and `Error::PortInvalid` if the RPC port is not a port number.
+    pub fn to_settings_json(&self) -> TrResult<String> {
+        unsafe {
+            let mut variant = self.to_variant()?;
+            let mut len = 0;
+            let json = transmission_sys::tr_variantToStr(
+                &variant,
+                transmission_sys::tr_variant_fmt::TR_VARIANT_FMT_JSON,
+                &mut len,
+            );
+            let settings =
+                String::from_utf8_lossy(slice::from_raw_parts(json as *const u8, len)).into_owned();
+            transmission_sys::tr_free(json as *mut c_void);
+            transmission_sys::tr_variantFree(&mut variant);
+            Ok(settings)
         }
     }
 
@@ -49,14 +389,137 @@ impl ClientConfig {
     }
 
     /// Set the configuration directory path. Must be set.
-    pub fn config_dir(mut self, dir: &str) -> Self {
-        self.config_dir = Some(canonicalize(dir).unwrap());
-        self
+    ///
+    /// Panics if the directory can't be used, see `ClientConfig::try_config_dir()`.
+    pub fn config_dir(self, dir: &str) -> Self {
+        self.try_config_dir(dir).unwrap()
+    }
+
+    /// Set the configuration directory path.
+    ///
+    /// Fails with `Error::DirNotFound` if the directory does not exist
+    /// and `Error::PathNotUtf8` if its full path is not valid UTF-8.
+    pub fn try_config_dir(mut self, dir: &str) -> TrResult<Self> {
+        self.config_dir = Some(existing_dir(dir)?);
+        Ok(self)
     }
 
     /// Set the download directory path. Must be set.
-    pub fn download_dir(mut self, dir: &str) -> Self {
-        self.download_dir = Some(canonicalize(dir).unwrap());
+    ///
+    /// Panics if the directory can't be used, see `ClientConfig::try_download_dir()`.
+    pub fn download_dir(self, dir: &str) -> Self {
+        self.try_download_dir(dir).unwrap()
+    }
+
+    /// Set the download directory path.
+    ///
+    /// Fails with `Error::DirNotFound` if the directory does not exist
+    /// and `Error::PathNotUtf8` if its full path is not valid UTF-8.
+    pub fn try_download_dir(mut self, dir: &str) -> TrResult<Self> {
+        self.download_dir = Some(existing_dir(dir)?);
+        Ok(self)
+    }
+
+    /// Set the directory torrents are kept in until they are complete.
+    ///
+    /// Only used when enabled with `ClientConfig::incomplete_dir_enabled()`.
+    /// Panics if the directory can't be used, see `ClientConfig::try_incomplete_dir()`.
+    pub fn incomplete_dir(self, dir: &str) -> Self {
+        self.try_incomplete_dir(dir).unwrap()
+    }
+
+    /// Set the directory torrents are kept in until they are complete.
+    ///
+    /// Fails with `Error::DirNotFound` if the directory does not exist
+    /// and `Error::PathNotUtf8` if its full path is not valid UTF-8.
+    pub fn try_incomplete_dir(mut self, dir: &str) -> TrResult<Self> {
+        self.incomplete_dir = Some(existing_dir(dir)?);
+        Ok(self)
+    }
+
+    /// Toggle keeping incomplete torrents in the incomplete directory.
+    pub fn incomplete_dir_enabled(mut self, enabled: bool) -> Self {
+        self.incomplete_dir_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle adding `.part` to the names of incomplete files.
+    pub fn rename_partial_files(mut self, rename: bool) -> Self {
+        self.rename_partial_files = Some(rename);
+        self
+    }
+
+    /// Set how disk space is reserved for new files.
+    pub fn preallocation(mut self, mode: Preallocation) -> Self {
+        self.preallocation = Some(mode);
+        self
+    }
+
+    /// Set the umask used for new files, for example `0o022`.
+    pub fn umask(mut self, umask: u32) -> Self {
+        self.umask = Some(umask);
+        self
+    }
+
+    /// Set the size of the disk cache in MB.
+    pub fn cache_size_mb(mut self, mb: u32) -> Self {
+        self.cache_size_mb = Some(mb);
+        self
+    }
+
+    /// Toggle reading ahead pieces that will be uploaded.
+    pub fn prefetch_enabled(mut self, enabled: bool) -> Self {
+        self.prefetch_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle starting torrents as soon as they are added.
+    pub fn start_added_torrents(mut self, start: bool) -> Self {
+        self.start_added_torrents = Some(start);
+        self
+    }
+
+    /// Toggle deleting .torrent files once they are added.
+    pub fn trash_original_torrent_files(mut self, trash: bool) -> Self {
+        self.trash_original_torrent_files = Some(trash);
+        self
+    }
+
+    /// Set the directory to add .torrent files from.
+    ///
+    /// The directory is watched by `transmission-daemon`, not the library,
+    /// but is kept with the rest of the settings.
+    /// Panics if the directory can't be used, see `ClientConfig::try_watch_dir()`.
+    pub fn watch_dir(self, dir: &str) -> Self {
+        self.try_watch_dir(dir).unwrap()
+    }
+
+    /// Set the directory to add .torrent files from.
+    ///
+    /// Fails with `Error::DirNotFound` if the directory does not exist
+    /// and `Error::PathNotUtf8` if its full path is not valid UTF-8.
+    pub fn try_watch_dir(mut self, dir: &str) -> TrResult<Self> {
+        self.watch_dir = Some(existing_dir(dir)?);
+        Ok(self)
+    }
+
+    /// Toggle adding .torrent files from the watch directory.
+    pub fn watch_dir_enabled(mut self, enabled: bool) -> Self {
+        self.watch_dir_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the script to run when a torrent is done.
+    ///
+    /// Only used when enabled with `ClientConfig::script_torrent_done_enabled()`.
+    pub fn script_torrent_done_filename(mut self, path: &str) -> Self {
+        self.script_torrent_done_filename = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Toggle running the script when a torrent is done.
+    pub fn script_torrent_done_enabled(mut self, enabled: bool) -> Self {
+        self.script_torrent_done_enabled = Some(enabled);
         self
     }
 
@@ -79,78 +542,1139 @@ impl ClientConfig {
         self
     }
 
+    /// Set the port to listen for peers on.
+    pub fn peer_port(mut self, port: u16) -> Self {
+        self.peer_port = Some(port);
+        self
+    }
+
+    /// Toggle picking a random peer port on start.
+    pub fn peer_port_random_on_start(mut self, random: bool) -> Self {
+        self.peer_port_random_on_start = Some(random);
+        self
+    }
+
+    /// Set the lowest port picked as a random peer port.
+    pub fn peer_port_random_low(mut self, port: u16) -> Self {
+        self.peer_port_random_low = Some(port);
+        self
+    }
+
+    /// Set the highest port picked as a random peer port.
+    pub fn peer_port_random_high(mut self, port: u16) -> Self {
+        self.peer_port_random_high = Some(port);
+        self
+    }
+
+    /// Toggle forwarding the peer port with UPnP or NAT-PMP.
+    pub fn port_forwarding_enabled(mut self, enabled: bool) -> Self {
+        self.port_forwarding_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the IPv4 address to listen for peers on.
+    pub fn bind_address_ipv4(mut self, address: &str) -> Self {
+        self.bind_address_ipv4 = Some(String::from(address));
+        self
+    }
+
+    /// Set the IPv6 address to listen for peers on.
+    pub fn bind_address_ipv6(mut self, address: &str) -> Self {
+        self.bind_address_ipv6 = Some(String::from(address));
+        self
+    }
+
+    /// Set the maximum number of peers over all torrents.
+    pub fn peer_limit_global(mut self, limit: u16) -> Self {
+        self.peer_limit_global = Some(limit);
+        self
+    }
+
+    /// Set the maximum number of peers per torrent.
+    pub fn peer_limit_per_torrent(mut self, limit: u16) -> Self {
+        self.peer_limit_per_torrent = Some(limit);
+        self
+    }
+
+    /// Set the maximum number of peers uploaded to per torrent.
+    pub fn upload_slots_per_torrent(mut self, slots: u32) -> Self {
+        self.upload_slots_per_torrent = Some(slots);
+        self
+    }
+
+    /// Set the TCP congestion algorithm used for peers, for example `"lp"`.
+    pub fn peer_congestion_algorithm(mut self, algorithm: &str) -> Self {
+        self.peer_congestion_algorithm = Some(String::from(algorithm));
+        self
+    }
+
+    /// Set the type of service of peer connections.
+    ///
+    /// One of `"default"`, `"lowcost"`, `"throughput"`, `"lowdelay"` or `"reliability"`.
+    pub fn peer_socket_tos(mut self, tos: &str) -> Self {
+        self.peer_socket_tos = Some(String::from(tos));
+        self
+    }
+
+    /// Set how many hours a peer ID is used for on public torrents.
+    pub fn peer_id_ttl_hours(mut self, hours: u32) -> Self {
+        self.peer_id_ttl_hours = Some(hours);
+        self
+    }
+
+    /// Set how peer connections are encrypted.
+    pub fn encryption(mut self, mode: Encryption) -> Self {
+        self.encryption = Some(mode);
+        self
+    }
+
+    /// Toggle using DHT to find peers.
+    pub fn dht_enabled(mut self, enabled: bool) -> Self {
+        self.dht_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle using peer exchange to find peers.
+    pub fn pex_enabled(mut self, enabled: bool) -> Self {
+        self.pex_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle using local peer discovery to find peers.
+    pub fn lpd_enabled(mut self, enabled: bool) -> Self {
+        self.lpd_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle scraping the trackers of stopped torrents.
+    pub fn scrape_paused_torrents_enabled(mut self, enabled: bool) -> Self {
+        self.scrape_paused_torrents_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle using the blocklist.
+    pub fn blocklist_enabled(mut self, enabled: bool) -> Self {
+        self.blocklist_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the URL to update the blocklist from.
+    pub fn blocklist_url(mut self, url: &str) -> Self {
+        self.blocklist_url = Some(String::from(url));
+        self
+    }
+
+    /// Toggle the RPC server. The other RPC options are kept even when it is disabled.
     pub fn rpc_enabled(mut self, rpc: bool) -> Self {
         self.rpc_enabled = rpc;
         self
     }
 
+    /// Set the URL the RPC will serve on.
     pub fn rpc_url(mut self, rpc_url: String) -> Self {
         self.rpc_url = Some(rpc_url);
         self
     }
 
+    /// Set the port the RPC will serve on.
+    ///
+    /// Creating a `Client` fails with `Error::PortInvalid` if it is not a valid port number.
     pub fn rpc_port(mut self, rpc_port: String) -> Self {
         self.rpc_port = Some(rpc_port);
         self
     }
 
-    pub(crate) unsafe fn to_variant(self) -> transmission_sys::tr_variant {
-        let mut variant: transmission_sys::tr_variant = mem::uninitialized();
-        transmission_sys::tr_variantInitDict(&mut variant, 0);
+    /// Set the address the RPC will serve on.
+    pub fn rpc_bind_address(mut self, address: &str) -> Self {
+        self.rpc_bind_address = Some(String::from(address));
+        self
+    }
 
-        // Set the download directory
-        if let Some(download_dir) = self.download_dir {
-            let d_dir = CString::new(download_dir.to_str().unwrap()).unwrap();
-            transmission_sys::tr_variantDictAddStr(
-                &mut variant,
-                transmission_sys::TR_KEY_download_dir as usize,
-                d_dir.as_ptr(),
-            );
+    /// Toggle requiring a username and password for the RPC.
+    pub fn rpc_authentication_required(mut self, required: bool) -> Self {
+        self.rpc_authentication_required = Some(required);
+        self
+    }
+
+    /// Set the RPC username.
+    pub fn rpc_username(mut self, username: &str) -> Self {
+        self.rpc_username = Some(String::from(username));
+        self
+    }
+
+    /// Set the RPC password.
+    pub fn rpc_password(mut self, password: &str) -> Self {
+        self.rpc_password = Some(String::from(password));
+        self
+    }
+
+    /// Set the IP addresses allowed to use the RPC. `*` can be used as a wildcard.
+    pub fn rpc_whitelist(mut self, addresses: Vec<&str>) -> Self {
+        self.rpc_whitelist = Some(addresses.into_iter().map(String::from).collect());
+        self
+    }
+
+    /// Toggle the RPC whitelist.
+    pub fn rpc_whitelist_enabled(mut self, enabled: bool) -> Self {
+        self.rpc_whitelist_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the host names allowed to use the RPC.
+    pub fn rpc_host_whitelist(mut self, hosts: Vec<&str>) -> Self {
+        self.rpc_host_whitelist = Some(hosts.into_iter().map(String::from).collect());
+        self
+    }
+
+    /// Toggle the RPC host whitelist.
+    pub fn rpc_host_whitelist_enabled(mut self, enabled: bool) -> Self {
+        self.rpc_host_whitelist_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the upload speed limit in KB/s.
+    pub fn speed_limit_up(mut self, kbps: u32) -> Self {
+        self.speed_limit_up = Some(kbps);
+        self
+    }
+
+    /// Toggle limiting the upload speed.
+    pub fn speed_limit_up_enabled(mut self, enabled: bool) -> Self {
+        self.speed_limit_up_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the download speed limit in KB/s.
+    pub fn speed_limit_down(mut self, kbps: u32) -> Self {
+        self.speed_limit_down = Some(kbps);
+        self
+    }
+
+    /// Toggle limiting the download speed.
+    pub fn speed_limit_down_enabled(mut self, enabled: bool) -> Self {
+        self.speed_limit_down_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the alternative ("turtle mode") upload speed limit in KB/s.
+    pub fn alt_speed_up(mut self, kbps: u32) -> Self {
+        self.alt_speed_up = Some(kbps);
+        self
+    }
+
+    /// Set the alternative ("turtle mode") download speed limit in KB/s.
+    pub fn alt_speed_down(mut self, kbps: u32) -> Self {
+        self.alt_speed_down = Some(kbps);
+        self
+    }
+
+    /// Toggle using the alternative speed limits.
+    pub fn alt_speed_enabled(mut self, enabled: bool) -> Self {
+        self.alt_speed_enabled = Some(enabled);
+        self
+    }
+
+    /// Set when to automatically use the alternative speed limits.
+    pub fn alt_speed_schedule(mut self, schedule: AltSpeedSchedule) -> Self {
+        self.alt_speed_schedule = Some(schedule);
+        self
+    }
+
+    /// Toggle using the alternative speed schedule.
+    pub fn alt_speed_schedule_enabled(mut self, enabled: bool) -> Self {
+        self.alt_speed_schedule_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the seed ratio limit used by torrents in `RatioMode::Global`.
+    pub fn ratio_limit(mut self, ratio: f64) -> Self {
+        self.ratio_limit = Some(ratio);
+        self
+    }
+
+    /// Toggle limiting the seed ratio of torrents in `RatioMode::Global`.
+    pub fn ratio_limit_enabled(mut self, enabled: bool) -> Self {
+        self.ratio_limit_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the idle seeding limit in minutes used by torrents in `IdleMode::Global`.
+    pub fn idle_seeding_limit(mut self, minutes: u16) -> Self {
+        self.idle_seeding_limit = Some(minutes);
+        self
+    }
+
+    /// Toggle limiting the idle seeding time of torrents in `IdleMode::Global`.
+    pub fn idle_seeding_limit_enabled(mut self, enabled: bool) -> Self {
+        self.idle_seeding_limit_enabled = Some(enabled);
+        self
+    }
+
+    /// Set how many torrents can download at once.
+    pub fn download_queue_size(mut self, size: u32) -> Self {
+        self.download_queue_size = Some(size);
+        self
+    }
+
+    /// Toggle limiting the number of downloading torrents.
+    pub fn download_queue_enabled(mut self, enabled: bool) -> Self {
+        self.download_queue_enabled = Some(enabled);
+        self
+    }
+
+    /// Set how many torrents can seed at once.
+    pub fn seed_queue_size(mut self, size: u32) -> Self {
+        self.seed_queue_size = Some(size);
+        self
+    }
+
+    /// Toggle limiting the number of seeding torrents.
+    pub fn seed_queue_enabled(mut self, enabled: bool) -> Self {
+        self.seed_queue_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the minutes without activity before a torrent is stalled.
+    ///
+    /// Stalled torrents don't count against the queue sizes.
+    pub fn queue_stalled_minutes(mut self, minutes: u32) -> Self {
+        self.queue_stalled_minutes = Some(minutes);
+        self
+    }
+
+    /// Toggle marking inactive torrents as stalled.
+    pub fn queue_stalled_enabled(mut self, enabled: bool) -> Self {
+        self.queue_stalled_enabled = Some(enabled);
+        self
+    }
+
+    /// The config as a settings dictionary, which has to be freed with `tr_variantFree()`
+    ///
+    /// Fails with `Error::StringNul` if a string contains a NUL byte,
+    /// `Error::PathNotUtf8` if a path can't be given to Transmission
+    /// and `Error::PortInvalid` if the RPC port is not a port number.
+    pub(crate) unsafe fn to_variant(&self) -> TrResult<transmission_sys::tr_variant> {
+        let mut variant: transmission_sys::tr_variant = mem::zeroed();
+        transmission_sys::tr_variantInitDict(&mut variant, 0);
+        match self.add_to_variant(&mut variant) {
+            Ok(()) => Ok(variant),
+            Err(e) => {
+                transmission_sys::tr_variantFree(&mut variant);
+                Err(e)
+            }
         }
+    }
+
+    /// Add the options that are set to a settings dictionary
+    unsafe fn add_to_variant(&self, v: &mut transmission_sys::tr_variant) -> TrResult<()> {
+        // Set the files and directories
+        add_path(
+            v,
+            transmission_sys::TR_KEY_download_dir as usize,
+            &self.download_dir,
+        )?;
+        add_path(
+            v,
+            transmission_sys::TR_KEY_incomplete_dir as usize,
+            &self.incomplete_dir,
+        )?;
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_incomplete_dir_enabled as usize,
+            self.incomplete_dir_enabled,
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_rename_partial_files as usize,
+            self.rename_partial_files,
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_preallocation as usize,
+            self.preallocation
+                .map(|p| transmission_sys::tr_preallocation_mode::from(p) as i64),
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_umask as usize,
+            self.umask.map(i64::from),
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_cache_size_mb as usize,
+            self.cache_size_mb.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_prefetch_enabled as usize,
+            self.prefetch_enabled,
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_start_added_torrents as usize,
+            self.start_added_torrents,
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_trash_original_torrent_files as usize,
+            self.trash_original_torrent_files,
+        );
+        add_path(
+            v,
+            transmission_sys::TR_KEY_watch_dir as usize,
+            &self.watch_dir,
+        )?;
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_watch_dir_enabled as usize,
+            self.watch_dir_enabled,
+        );
+        add_path(
+            v,
+            transmission_sys::TR_KEY_script_torrent_done_filename as usize,
+            &self.script_torrent_done_filename,
+        )?;
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_script_torrent_done_enabled as usize,
+            self.script_torrent_done_enabled,
+        );
 
         // Set the UTP
-        transmission_sys::tr_variantDictAddBool(
-            &mut variant,
+        add_bool(
+            v,
             transmission_sys::TR_KEY_utp_enabled as usize,
-            self.use_utp,
+            Some(self.use_utp),
         );
 
         // Set the log level
-        transmission_sys::tr_variantDictAddInt(
-            &mut variant,
+        add_int(
+            v,
             transmission_sys::TR_KEY_message_level as usize,
-            self.log_level,
+            Some(self.log_level),
         );
 
+        // Set the peer connections
+        add_int(
+            v,
+            transmission_sys::TR_KEY_peer_port as usize,
+            self.peer_port.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_peer_port_random_on_start as usize,
+            self.peer_port_random_on_start,
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_peer_port_random_low as usize,
+            self.peer_port_random_low.map(i64::from),
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_peer_port_random_high as usize,
+            self.peer_port_random_high.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_port_forwarding_enabled as usize,
+            self.port_forwarding_enabled,
+        );
+        add_str(
+            v,
+            transmission_sys::TR_KEY_bind_address_ipv4 as usize,
+            &self.bind_address_ipv4,
+        )?;
+        add_str(
+            v,
+            transmission_sys::TR_KEY_bind_address_ipv6 as usize,
+            &self.bind_address_ipv6,
+        )?;
+        add_int(
+            v,
+            transmission_sys::TR_KEY_peer_limit_global as usize,
+            self.peer_limit_global.map(i64::from),
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_peer_limit_per_torrent as usize,
+            self.peer_limit_per_torrent.map(i64::from),
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_upload_slots_per_torrent as usize,
+            self.upload_slots_per_torrent.map(i64::from),
+        );
+        add_str(
+            v,
+            transmission_sys::TR_KEY_peer_congestion_algorithm as usize,
+            &self.peer_congestion_algorithm,
+        )?;
+        add_str(
+            v,
+            transmission_sys::TR_KEY_peer_socket_tos as usize,
+            &self.peer_socket_tos,
+        )?;
+        add_int(
+            v,
+            transmission_sys::TR_KEY_peer_id_ttl_hours as usize,
+            self.peer_id_ttl_hours.map(i64::from),
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_encryption as usize,
+            self.encryption
+                .map(|e| transmission_sys::tr_encryption_mode::from(e) as i64),
+        );
+
+        // Set the peer discovery
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_dht_enabled as usize,
+            self.dht_enabled,
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_pex_enabled as usize,
+            self.pex_enabled,
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_lpd_enabled as usize,
+            self.lpd_enabled,
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_scrape_paused_torrents_enabled as usize,
+            self.scrape_paused_torrents_enabled,
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_blocklist_enabled as usize,
+            self.blocklist_enabled,
+        );
+        add_str(
+            v,
+            transmission_sys::TR_KEY_blocklist_url as usize,
+            &self.blocklist_url,
+        )?;
+
         // Set RPC
-        transmission_sys::tr_variantDictAddBool(
-            &mut variant,
+        add_bool(
+            v,
             transmission_sys::TR_KEY_rpc_enabled as usize,
-            self.rpc_enabled,
-        );
-
-        if self.rpc_enabled {
-            // Set RPC URL
-            if let Some(rpc_url) = self.rpc_url {
-                let r_url = CString::new(rpc_url).unwrap();
-                transmission_sys::tr_variantDictAddStr(
-                    &mut variant,
-                    transmission_sys::TR_KEY_rpc_url as usize,
-                    r_url.as_ptr(),
-                );
-            }
+            Some(self.rpc_enabled),
+        );
 
-            // Set RPC port
-            if let Some(rpc_port) = self.rpc_port {
-                let r_port = CString::new(rpc_port).unwrap();
-                transmission_sys::tr_variantDictAddStr(
-                    &mut variant,
-                    transmission_sys::TR_KEY_rpc_port as usize,
-                    r_port.as_ptr(),
-                );
-            }
+        add_str(v, transmission_sys::TR_KEY_rpc_url as usize, &self.rpc_url)?;
+        let rpc_port = self
+            .rpc_port
+            .as_ref()
+            .map(|port| port.trim().parse::<u16>().map_err(|_| Error::PortInvalid))
+            .transpose()?;
+        add_int(
+            v,
+            transmission_sys::TR_KEY_rpc_port as usize,
+            rpc_port.map(i64::from),
+        );
+        add_str(
+            v,
+            transmission_sys::TR_KEY_rpc_bind_address as usize,
+            &self.rpc_bind_address,
+        )?;
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_rpc_authentication_required as usize,
+            self.rpc_authentication_required,
+        );
+        add_str(
+            v,
+            transmission_sys::TR_KEY_rpc_username as usize,
+            &self.rpc_username,
+        )?;
+        add_str(
+            v,
+            transmission_sys::TR_KEY_rpc_password as usize,
+            &self.rpc_password,
+        )?;
+        // The whitelists are stored as comma separated strings
+        add_str(
+            v,
+            transmission_sys::TR_KEY_rpc_whitelist as usize,
+            &self.rpc_whitelist.as_ref().map(|l| l.join(",")),
+        )?;
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_rpc_whitelist_enabled as usize,
+            self.rpc_whitelist_enabled,
+        );
+        add_str(
+            v,
+            transmission_sys::TR_KEY_rpc_host_whitelist as usize,
+            &self.rpc_host_whitelist.as_ref().map(|l| l.join(",")),
+        )?;
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_rpc_host_whitelist_enabled as usize,
+            self.rpc_host_whitelist_enabled,
+        );
+
+        // Set the speed limits
+        add_int(
+            v,
+            transmission_sys::TR_KEY_speed_limit_up as usize,
+            self.speed_limit_up.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_speed_limit_up_enabled as usize,
+            self.speed_limit_up_enabled,
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_speed_limit_down as usize,
+            self.speed_limit_down.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_speed_limit_down_enabled as usize,
+            self.speed_limit_down_enabled,
+        );
+
+        // Set the alternative speed limits
+        add_int(
+            v,
+            transmission_sys::TR_KEY_alt_speed_up as usize,
+            self.alt_speed_up.map(i64::from),
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_alt_speed_down as usize,
+            self.alt_speed_down.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_alt_speed_enabled as usize,
+            self.alt_speed_enabled,
+        );
+        if let Some(schedule) = &self.alt_speed_schedule {
+            add_int(
+                v,
+                transmission_sys::TR_KEY_alt_speed_time_day as usize,
+                Some(i64::from(schedule.day_mask())),
+            );
+            add_int(
+                v,
+                transmission_sys::TR_KEY_alt_speed_time_begin as usize,
+                Some(i64::from(schedule.begin_minutes())),
+            );
+            add_int(
+                v,
+                transmission_sys::TR_KEY_alt_speed_time_end as usize,
+                Some(i64::from(schedule.end_minutes())),
+            );
+        }
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_alt_speed_time_enabled as usize,
+            self.alt_speed_schedule_enabled,
+        );
+
+        // Set the seeding limits
+        add_real(
+            v,
+            transmission_sys::TR_KEY_ratio_limit as usize,
+            self.ratio_limit,
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_ratio_limit_enabled as usize,
+            self.ratio_limit_enabled,
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_idle_seeding_limit as usize,
+            self.idle_seeding_limit.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_idle_seeding_limit_enabled as usize,
+            self.idle_seeding_limit_enabled,
+        );
+
+        // Set the queues
+        add_int(
+            v,
+            transmission_sys::TR_KEY_download_queue_size as usize,
+            self.download_queue_size.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_download_queue_enabled as usize,
+            self.download_queue_enabled,
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_seed_queue_size as usize,
+            self.seed_queue_size.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_seed_queue_enabled as usize,
+            self.seed_queue_enabled,
+        );
+        add_int(
+            v,
+            transmission_sys::TR_KEY_queue_stalled_minutes as usize,
+            self.queue_stalled_minutes.map(i64::from),
+        );
+        add_bool(
+            v,
+            transmission_sys::TR_KEY_queue_stalled_enabled as usize,
+            self.queue_stalled_enabled,
+        );
+
+        Ok(())
+    }
+
+    /// Read a config back from a settings dictionary
+    ///
+    /// Keys missing from the dictionary are left unset.
+    pub(crate) unsafe fn from_variant(variant: *mut transmission_sys::tr_variant) -> Self {
+        let v = variant;
+        let mut config = Self::new();
+
+        config.download_dir =
+            find_str(v, transmission_sys::TR_KEY_download_dir as usize).map(PathBuf::from);
+        config.incomplete_dir =
+            find_str(v, transmission_sys::TR_KEY_incomplete_dir as usize).map(PathBuf::from);
+        config.incomplete_dir_enabled =
+            find_bool(v, transmission_sys::TR_KEY_incomplete_dir_enabled as usize);
+        config.rename_partial_files =
+            find_bool(v, transmission_sys::TR_KEY_rename_partial_files as usize);
+        config.preallocation = find_int(v, transmission_sys::TR_KEY_preallocation as usize)
+            .and_then(Preallocation::from_raw);
+        config.umask = find_int(v, transmission_sys::TR_KEY_umask as usize)
+            .and_then(|i| u32::try_from(i).ok());
+        config.cache_size_mb = find_int(v, transmission_sys::TR_KEY_cache_size_mb as usize)
+            .and_then(|i| u32::try_from(i).ok());
+        config.prefetch_enabled = find_bool(v, transmission_sys::TR_KEY_prefetch_enabled as usize);
+        config.start_added_torrents =
+            find_bool(v, transmission_sys::TR_KEY_start_added_torrents as usize);
+        config.trash_original_torrent_files = find_bool(
+            v,
+            transmission_sys::TR_KEY_trash_original_torrent_files as usize,
+        );
+        config.watch_dir =
+            find_str(v, transmission_sys::TR_KEY_watch_dir as usize).map(PathBuf::from);
+        config.watch_dir_enabled =
+            find_bool(v, transmission_sys::TR_KEY_watch_dir_enabled as usize);
+        config.script_torrent_done_filename = find_str(
+            v,
+            transmission_sys::TR_KEY_script_torrent_done_filename as usize,
+        )
+        .map(PathBuf::from);
+        config.script_torrent_done_enabled = find_bool(
+            v,
+            transmission_sys::TR_KEY_script_torrent_done_enabled as usize,
+        );
+
+        if let Some(utp) = find_bool(v, transmission_sys::TR_KEY_utp_enabled as usize) {
+            config.use_utp = utp;
+        }
+        if let Some(level) = find_int(v, transmission_sys::TR_KEY_message_level as usize) {
+            config.log_level = level;
+        }
+
+        config.peer_port = find_int(v, transmission_sys::TR_KEY_peer_port as usize)
+            .and_then(|i| u16::try_from(i).ok());
+        config.peer_port_random_on_start = find_bool(
+            v,
+            transmission_sys::TR_KEY_peer_port_random_on_start as usize,
+        );
+        config.peer_port_random_low =
+            find_int(v, transmission_sys::TR_KEY_peer_port_random_low as usize)
+                .and_then(|i| u16::try_from(i).ok());
+        config.peer_port_random_high =
+            find_int(v, transmission_sys::TR_KEY_peer_port_random_high as usize)
+                .and_then(|i| u16::try_from(i).ok());
+        config.port_forwarding_enabled =
+            find_bool(v, transmission_sys::TR_KEY_port_forwarding_enabled as usize);
+        config.bind_address_ipv4 = find_str(v, transmission_sys::TR_KEY_bind_address_ipv4 as usize);
+        config.bind_address_ipv6 = find_str(v, transmission_sys::TR_KEY_bind_address_ipv6 as usize);
+        config.peer_limit_global = find_int(v, transmission_sys::TR_KEY_peer_limit_global as usize)
+            .and_then(|i| u16::try_from(i).ok());
+        config.peer_limit_per_torrent =
+            find_int(v, transmission_sys::TR_KEY_peer_limit_per_torrent as usize)
+                .and_then(|i| u16::try_from(i).ok());
+        config.upload_slots_per_torrent = find_int(
+            v,
+            transmission_sys::TR_KEY_upload_slots_per_torrent as usize,
+        )
+        .and_then(|i| u32::try_from(i).ok());
+        config.peer_congestion_algorithm = find_str(
+            v,
+            transmission_sys::TR_KEY_peer_congestion_algorithm as usize,
+        );
+        config.peer_socket_tos = find_str(v, transmission_sys::TR_KEY_peer_socket_tos as usize);
+        config.peer_id_ttl_hours = find_int(v, transmission_sys::TR_KEY_peer_id_ttl_hours as usize)
+            .and_then(|i| u32::try_from(i).ok());
+        config.encryption = find_int(v, transmission_sys::TR_KEY_encryption as usize)
+            .and_then(Encryption::from_raw);
+
+        config.dht_enabled = find_bool(v, transmission_sys::TR_KEY_dht_enabled as usize);
+        config.pex_enabled = find_bool(v, transmission_sys::TR_KEY_pex_enabled as usize);
+        config.lpd_enabled = find_bool(v, transmission_sys::TR_KEY_lpd_enabled as usize);
+        config.scrape_paused_torrents_enabled = find_bool(
+            v,
+            transmission_sys::TR_KEY_scrape_paused_torrents_enabled as usize,
+        );
+        config.blocklist_enabled =
+            find_bool(v, transmission_sys::TR_KEY_blocklist_enabled as usize);
+        config.blocklist_url = find_str(v, transmission_sys::TR_KEY_blocklist_url as usize);
+
+        if let Some(rpc) = find_bool(v, transmission_sys::TR_KEY_rpc_enabled as usize) {
+            config.rpc_enabled = rpc;
+        }
+        config.rpc_url = find_str(v, transmission_sys::TR_KEY_rpc_url as usize);
+        config.rpc_port = find_int(v, transmission_sys::TR_KEY_rpc_port as usize)
+            .and_then(|i| u16::try_from(i).ok())
+            .map(|port| port.to_string());
+        config.rpc_bind_address = find_str(v, transmission_sys::TR_KEY_rpc_bind_address as usize);
+        config.rpc_authentication_required = find_bool(
+            v,
+            transmission_sys::TR_KEY_rpc_authentication_required as usize,
+        );
+        config.rpc_username = find_str(v, transmission_sys::TR_KEY_rpc_username as usize);
+        config.rpc_password = find_str(v, transmission_sys::TR_KEY_rpc_password as usize);
+        config.rpc_whitelist =
+            find_str(v, transmission_sys::TR_KEY_rpc_whitelist as usize).map(split_list);
+        config.rpc_whitelist_enabled =
+            find_bool(v, transmission_sys::TR_KEY_rpc_whitelist_enabled as usize);
+        config.rpc_host_whitelist =
+            find_str(v, transmission_sys::TR_KEY_rpc_host_whitelist as usize).map(split_list);
+        config.rpc_host_whitelist_enabled = find_bool(
+            v,
+            transmission_sys::TR_KEY_rpc_host_whitelist_enabled as usize,
+        );
+
+        config.speed_limit_up = find_int(v, transmission_sys::TR_KEY_speed_limit_up as usize)
+            .and_then(|i| u32::try_from(i).ok());
+        config.speed_limit_up_enabled =
+            find_bool(v, transmission_sys::TR_KEY_speed_limit_up_enabled as usize);
+        config.speed_limit_down = find_int(v, transmission_sys::TR_KEY_speed_limit_down as usize)
+            .and_then(|i| u32::try_from(i).ok());
+        config.speed_limit_down_enabled = find_bool(
+            v,
+            transmission_sys::TR_KEY_speed_limit_down_enabled as usize,
+        );
+
+        config.alt_speed_up = find_int(v, transmission_sys::TR_KEY_alt_speed_up as usize)
+            .and_then(|i| u32::try_from(i).ok());
+        config.alt_speed_down = find_int(v, transmission_sys::TR_KEY_alt_speed_down as usize)
+            .and_then(|i| u32::try_from(i).ok());
+        config.alt_speed_enabled =
+            find_bool(v, transmission_sys::TR_KEY_alt_speed_enabled as usize);
+        let find_i32 = |key| find_int(v, key as usize).and_then(|i| i32::try_from(i).ok());
+        if let (Some(day), Some(begin), Some(end)) = (
+            find_i32(transmission_sys::TR_KEY_alt_speed_time_day),
+            find_i32(transmission_sys::TR_KEY_alt_speed_time_begin),
+            find_i32(transmission_sys::TR_KEY_alt_speed_time_end),
+        ) {
+            config.alt_speed_schedule = Some(AltSpeedSchedule::from_raw(day, begin, end));
         }
+        config.alt_speed_schedule_enabled =
+            find_bool(v, transmission_sys::TR_KEY_alt_speed_time_enabled as usize);
+
+        config.ratio_limit = find_real(v, transmission_sys::TR_KEY_ratio_limit as usize);
+        config.ratio_limit_enabled =
+            find_bool(v, transmission_sys::TR_KEY_ratio_limit_enabled as usize);
+        config.idle_seeding_limit =
+            find_int(v, transmission_sys::TR_KEY_idle_seeding_limit as usize)
+                .and_then(|i| u16::try_from(i).ok());
+        config.idle_seeding_limit_enabled = find_bool(
+            v,
+            transmission_sys::TR_KEY_idle_seeding_limit_enabled as usize,
+        );
+
+        config.download_queue_size =
+            find_int(v, transmission_sys::TR_KEY_download_queue_size as usize)
+                .and_then(|i| u32::try_from(i).ok());
+        config.download_queue_enabled =
+            find_bool(v, transmission_sys::TR_KEY_download_queue_enabled as usize);
+        config.seed_queue_size = find_int(v, transmission_sys::TR_KEY_seed_queue_size as usize)
+            .and_then(|i| u32::try_from(i).ok());
+        config.seed_queue_enabled =
+            find_bool(v, transmission_sys::TR_KEY_seed_queue_enabled as usize);
+        config.queue_stalled_minutes =
+            find_int(v, transmission_sys::TR_KEY_queue_stalled_minutes as usize)
+                .and_then(|i| u32::try_from(i).ok());
+        config.queue_stalled_enabled =
+            find_bool(v, transmission_sys::TR_KEY_queue_stalled_enabled as usize);
+
+        config
+    }
+}
+
+/// The full path of a directory that has to exist
+pub(super) fn existing_dir<P: AsRef<Path>>(dir: P) -> TrResult<PathBuf> {
+    let path = canonicalize(dir).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => Error::DirNotFound,
+        _ => Error::IOError,
+    })?;
+    if !path.is_dir() {
+        return Err(Error::DirNotFound);
+    }
+    // Transmission only takes the paths as strings
+    path.to_str().ok_or(Error::PathNotUtf8)?;
+    Ok(path)
+}
+
+/// Add an integer to a settings dictionary if it is set
+unsafe fn add_int(
+    variant: &mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+    value: Option<i64>,
+) {
+    if let Some(value) = value {
+        transmission_sys::tr_variantDictAddInt(variant, key, value);
+    }
+}
+
+/// Add a boolean to a settings dictionary if it is set
+unsafe fn add_bool(
+    variant: &mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+    value: Option<bool>,
+) {
+    if let Some(value) = value {
+        transmission_sys::tr_variantDictAddBool(variant, key, value);
+    }
+}
+
+/// Add a real number to a settings dictionary if it is set
+unsafe fn add_real(
+    variant: &mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+    value: Option<f64>,
+) {
+    if let Some(value) = value {
+        transmission_sys::tr_variantDictAddReal(variant, key, value);
+    }
+}
+
+/// Add a string to a settings dictionary if it is set
+unsafe fn add_str(
+    variant: &mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+    value: &Option<String>,
+) -> TrResult<()> {
+    if let Some(value) = value {
+        // The dictionary keeps its own copy of the string
+        let value = CString::new(value.as_str()).map_err(|_| Error::StringNul)?;
+        transmission_sys::tr_variantDictAddStr(variant, key, value.as_ptr());
+    }
+    Ok(())
+}
+
+/// Add a path to a settings dictionary if it is set
+unsafe fn add_path(
+    variant: &mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+    value: &Option<PathBuf>,
+) -> TrResult<()> {
+    if let Some(value) = value {
+        let value = value.to_str().ok_or(Error::PathNotUtf8)?;
+        let value = CString::new(value).map_err(|_| Error::PathNotUtf8)?;
+        transmission_sys::tr_variantDictAddStr(variant, key, value.as_ptr());
+    }
+    Ok(())
+}
+
+/// Find an integer in a settings dictionary
+unsafe fn find_int(
+    variant: *mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+) -> Option<i64> {
+    let mut value = 0;
+    if transmission_sys::tr_variantDictFindInt(variant, key, &mut value) {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Find a boolean in a settings dictionary
+unsafe fn find_bool(
+    variant: *mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+) -> Option<bool> {
+    let mut value = false;
+    if transmission_sys::tr_variantDictFindBool(variant, key, &mut value) {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Find a real number in a settings dictionary
+unsafe fn find_real(
+    variant: *mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+) -> Option<f64> {
+    let mut value = 0.0;
+    if transmission_sys::tr_variantDictFindReal(variant, key, &mut value) {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+/// Find a string in a settings dictionary
+unsafe fn find_str(
+    variant: *mut transmission_sys::tr_variant,
+    key: transmission_sys::tr_quark,
+) -> Option<String> {
+    let mut value: *const c_char = null();
+    let mut len = 0;
+    if transmission_sys::tr_variantDictFindStr(variant, key, &mut value, &mut len) {
+        let bytes = slice::from_raw_parts(value as *const u8, len);
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    } else {
+        None
+    }
+}
+
+/// Split a comma separated list from the settings
+fn split_list(list: String) -> Vec<String> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::prelude::{NaiveTime, Weekday};
+
+    /// Write a config to a settings dictionary and read it back
+    fn round_trip(config: &ClientConfig) -> ClientConfig {
+        unsafe {
+            let mut variant = config.to_variant().unwrap();
+            let parsed = ClientConfig::from_variant(&mut variant);
+            transmission_sys::tr_variantFree(&mut variant);
+            parsed
+        }
+    }
+
+    #[test]
+    fn variant_round_trip() {
+        let config = ClientConfig::new()
+            .download_dir("/tmp")
+            .incomplete_dir("/tmp")
+            .incomplete_dir_enabled(true)
+            .rename_partial_files(false)
+            .preallocation(Preallocation::Full)
+            .umask(0o022)
+            .cache_size_mb(16)
+            .prefetch_enabled(false)
+            .start_added_torrents(false)
+            .trash_original_torrent_files(true)
+            .watch_dir("/tmp")
+            .watch_dir_enabled(true)
+            .script_torrent_done_filename("/tmp/done.sh")
+            .script_torrent_done_enabled(true)
+            .use_utp(false)
+            .log_level(2)
+            .peer_port(51414)
+            .peer_port_random_on_start(true)
+            .peer_port_random_low(50000)
+            .peer_port_random_high(60000)
+            .port_forwarding_enabled(false)
+            .bind_address_ipv4("127.0.0.1")
+            .bind_address_ipv6("::1")
+            .peer_limit_global(300)
+            .peer_limit_per_torrent(60)
+            .upload_slots_per_torrent(8)
+            .peer_congestion_algorithm("lp")
+            .peer_socket_tos("lowcost")
+            .peer_id_ttl_hours(12)
+            .encryption(Encryption::Required)
+            .dht_enabled(false)
+            .pex_enabled(false)
+            .lpd_enabled(true)
+            .scrape_paused_torrents_enabled(false)
+            .blocklist_enabled(true)
+            .blocklist_url("http://example.com/blocklist")
+            .rpc_enabled(true)
+            .rpc_url(String::from("/transmission/"))
+            .rpc_port(String::from("9092"))
+            .rpc_bind_address("127.0.0.1")
+            .rpc_authentication_required(true)
+            .rpc_username("user")
+            .rpc_password("pass")
+            .rpc_whitelist(vec!["127.0.0.1", "192.168.*.*"])
+            .rpc_whitelist_enabled(true)
+            .rpc_host_whitelist(vec!["localhost"])
+            .rpc_host_whitelist_enabled(true)
+            .speed_limit_up(100)
+            .speed_limit_up_enabled(true)
+            .speed_limit_down(200)
+            .speed_limit_down_enabled(true)
+            .alt_speed_up(10)
+            .alt_speed_down(20)
+            .alt_speed_enabled(true)
+            .alt_speed_schedule(AltSpeedSchedule::new(
+                vec![Weekday::Sat, Weekday::Sun],
+                NaiveTime::from_hms(8, 0, 0),
+                NaiveTime::from_hms(22, 30, 0),
+            ))
+            .alt_speed_schedule_enabled(true)
+            .ratio_limit(1.5)
+            .ratio_limit_enabled(true)
+            .idle_seeding_limit(45)
+            .idle_seeding_limit_enabled(true)
+            .download_queue_size(3)
+            .download_queue_enabled(true)
+            .seed_queue_size(4)
+            .seed_queue_enabled(true)
+            .queue_stalled_minutes(15)
+            .queue_stalled_enabled(false);
+
+        assert_eq!(round_trip(&config), config);
+    }
+
+    #[test]
+    fn variant_round_trip_rpc_disabled() {
+        let config = ClientConfig::new()
+            .download_dir("/tmp")
+            .rpc_enabled(false)
+            .rpc_port(String::from("9092"));
+
+        assert_eq!(round_trip(&config), config);
+    }
+
+    #[test]
+    fn settings_file_round_trip() {
+        let path = format!("/tmp/tr-test-settings-{}.json", std::process::id());
+        let path = path.as_str();
+        let config = ClientConfig::new()
+            .peer_port(51414)
+            .encryption(Encryption::Preferred)
+            .incomplete_dir("/tmp")
+            .ratio_limit(2.0);
+
+        std::fs::write(path, config.to_settings_json().unwrap()).unwrap();
+        let loaded = ClientConfig::from_settings_file(path);
+        std::fs::remove_file(path).unwrap_or(());
+        assert_eq!(loaded, Ok(config));
+    }
 
-        variant
+    #[test]
+    fn missing_dir() {
+        let config = ClientConfig::new().try_download_dir("/tmp/tr-test-missing-dir");
+        assert_eq!(config, Err(Error::DirNotFound));
     }
 }
@@ -0,0 +1,568 @@
+//! Contains the ClientConfigPatch builder.
+use std::convert::TryFrom;
+use std::ffi::CString;
+use std::os::raw::c_int;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use transmission_sys;
+
+use super::client::tr_sessionSetAltSpeedDayMask;
+use super::clientconfig::existing_dir;
+use super::{AltSpeedSchedule, Encryption};
+use crate::error::{Error, TrResult};
+
+/// Changes to the settings of a running `Client` made using a builder pattern.
+///
+/// Applied with `Client::apply()`, which only touches the options that are set.
+/// Only settings the session can change while running are available, the rest
+/// need a new `Client` with a `ClientConfig`.
+///
+/// ```
+/// use transmission::client::{ClientConfigPatch, Encryption};
+///
+/// let patch = ClientConfigPatch::new()
+///     .peer_port(51414)
+///     .encryption(Encryption::Required)
+///     .dht_enabled(false)
+///     .speed_limit_down(500)
+///     .speed_limit_down_enabled(true);
+/// ```
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientConfigPatch {
+    /// The path to the download directory
+    download_dir: Option<PathBuf>,
+    /// The path to keep torrents in until they are complete
+    incomplete_dir: Option<PathBuf>,
+    /// Are incomplete torrents kept in the incomplete directory?
+    incomplete_dir_enabled: Option<bool>,
+    /// Add `.part` to the names of incomplete files
+    rename_partial_files: Option<bool>,
+    /// Size of the disk cache in MB
+    cache_size_mb: Option<u32>,
+    /// Start torrents as soon as they are added
+    start_added_torrents: Option<bool>,
+    /// Delete .torrent files once they are added
+    trash_original_torrent_files: Option<bool>,
+    /// The script to run when a torrent is done
+    script_torrent_done_filename: Option<PathBuf>,
+    /// Is the torrent done script run?
+    script_torrent_done_enabled: Option<bool>,
+    /// Whether or not to use UTP
+    use_utp: Option<bool>,
+    /// The port to listen for peers on
+    peer_port: Option<u16>,
+    /// Pick a random peer port on start
+    peer_port_random_on_start: Option<bool>,
+    /// Forward the peer port with UPnP or NAT-PMP
+    port_forwarding_enabled: Option<bool>,
+    /// Maximum number of peers over all torrents
+    peer_limit_global: Option<u16>,
+    /// Maximum number of peers per torrent
+    peer_limit_per_torrent: Option<u16>,
+    /// How peer connections are encrypted
+    encryption: Option<Encryption>,
+    /// Whether or not to use DHT
+    dht_enabled: Option<bool>,
+    /// Whether or not to use peer exchange
+    pex_enabled: Option<bool>,
+    /// Whether or not to use local peer discovery
+    lpd_enabled: Option<bool>,
+    /// Is the blocklist used?
+    blocklist_enabled: Option<bool>,
+    /// The URL to update the blocklist from
+    blocklist_url: Option<String>,
+    /// Upload speed limit in KB/s
+    speed_limit_up: Option<u32>,
+    /// Is the upload speed limited?
+    speed_limit_up_enabled: Option<bool>,
+    /// Download speed limit in KB/s
+    speed_limit_down: Option<u32>,
+    /// Is the download speed limited?
+    speed_limit_down_enabled: Option<bool>,
+    /// Alternative upload speed limit in KB/s
+    alt_speed_up: Option<u32>,
+    /// Alternative download speed limit in KB/s
+    alt_speed_down: Option<u32>,
+    /// Are the alternative speed limits in use?
+    alt_speed_enabled: Option<bool>,
+    /// When to automatically use the alternative speed limits
+    alt_speed_schedule: Option<AltSpeedSchedule>,
+    /// Is the alternative speed schedule in use?
+    alt_speed_schedule_enabled: Option<bool>,
+    /// Default seed ratio limit
+    ratio_limit: Option<f64>,
+    /// Is the seed ratio limited by default?
+    ratio_limit_enabled: Option<bool>,
+    /// Default idle seeding limit in minutes
+    idle_seeding_limit: Option<u16>,
+    /// Is idle seeding limited by default?
+    idle_seeding_limit_enabled: Option<bool>,
+    /// How many torrents can download at once
+    download_queue_size: Option<u32>,
+    /// Is the number of downloading torrents limited?
+    download_queue_enabled: Option<bool>,
+    /// How many torrents can seed at once
+    seed_queue_size: Option<u32>,
+    /// Is the number of seeding torrents limited?
+    seed_queue_enabled: Option<bool>,
+    /// Minutes without activity before a torrent is stalled
+    queue_stalled_minutes: Option<u32>,
+    /// Can torrents become stalled?
+    queue_stalled_enabled: Option<bool>,
+}
+
+impl ClientConfigPatch {
+    /// Create a new, empty ClientConfigPatch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the download directory path.
+    ///
+    /// The directory has to exist when the patch is applied.
+    pub fn download_dir(mut self, dir: &str) -> Self {
+        self.download_dir = Some(PathBuf::from(dir));
+        self
+    }
+
+    /// Set the directory torrents are kept in until they are complete.
+    ///
+    /// The directory has to exist when the patch is applied.
+    pub fn incomplete_dir(mut self, dir: &str) -> Self {
+        self.incomplete_dir = Some(PathBuf::from(dir));
+        self
+    }
+
+    /// Toggle keeping incomplete torrents in the incomplete directory.
+    pub fn incomplete_dir_enabled(mut self, enabled: bool) -> Self {
+        self.incomplete_dir_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle adding `.part` to the names of incomplete files.
+    pub fn rename_partial_files(mut self, rename: bool) -> Self {
+        self.rename_partial_files = Some(rename);
+        self
+    }
+
+    /// Set the size of the disk cache in MB.
+    pub fn cache_size_mb(mut self, mb: u32) -> Self {
+        self.cache_size_mb = Some(mb);
+        self
+    }
+
+    /// Toggle starting torrents as soon as they are added.
+    pub fn start_added_torrents(mut self, start: bool) -> Self {
+        self.start_added_torrents = Some(start);
+        self
+    }
+
+    /// Toggle deleting .torrent files once they are added.
+    pub fn trash_original_torrent_files(mut self, trash: bool) -> Self {
+        self.trash_original_torrent_files = Some(trash);
+        self
+    }
+
+    /// Set the script to run when a torrent is done.
+    pub fn script_torrent_done_filename(mut self, path: &str) -> Self {
+        self.script_torrent_done_filename = Some(PathBuf::from(path));
+        self
+    }
+
+    /// Toggle running the script when a torrent is done.
+    pub fn script_torrent_done_enabled(mut self, enabled: bool) -> Self {
+        self.script_torrent_done_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle using UTP.
+    pub fn use_utp(mut self, utp: bool) -> Self {
+        self.use_utp = Some(utp);
+        self
+    }
+
+    /// Set the port to listen for peers on.
+    pub fn peer_port(mut self, port: u16) -> Self {
+        self.peer_port = Some(port);
+        self
+    }
+
+    /// Toggle picking a random peer port on start.
+    pub fn peer_port_random_on_start(mut self, random: bool) -> Self {
+        self.peer_port_random_on_start = Some(random);
+        self
+    }
+
+    /// Toggle forwarding the peer port with UPnP or NAT-PMP.
+    pub fn port_forwarding_enabled(mut self, enabled: bool) -> Self {
+        self.port_forwarding_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the maximum number of peers over all torrents.
+    pub fn peer_limit_global(mut self, limit: u16) -> Self {
+        self.peer_limit_global = Some(limit);
+        self
+    }
+
+    /// Set the maximum number of peers per torrent.
+    pub fn peer_limit_per_torrent(mut self, limit: u16) -> Self {
+        self.peer_limit_per_torrent = Some(limit);
+        self
+    }
+
+    /// Set how peer connections are encrypted.
+    pub fn encryption(mut self, mode: Encryption) -> Self {
+        self.encryption = Some(mode);
+        self
+    }
+
+    /// Toggle using DHT to find peers.
+    pub fn dht_enabled(mut self, enabled: bool) -> Self {
+        self.dht_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle using peer exchange to find peers.
+    pub fn pex_enabled(mut self, enabled: bool) -> Self {
+        self.pex_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle using local peer discovery to find peers.
+    pub fn lpd_enabled(mut self, enabled: bool) -> Self {
+        self.lpd_enabled = Some(enabled);
+        self
+    }
+
+    /// Toggle using the blocklist.
+    pub fn blocklist_enabled(mut self, enabled: bool) -> Self {
+        self.blocklist_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the URL to update the blocklist from.
+    pub fn blocklist_url(mut self, url: &str) -> Self {
+        self.blocklist_url = Some(String::from(url));
+        self
+    }
+
+    /// Set the upload speed limit in KB/s.
+    pub fn speed_limit_up(mut self, kbps: u32) -> Self {
+        self.speed_limit_up = Some(kbps);
+        self
+    }
+
+    /// Toggle limiting the upload speed.
+    pub fn speed_limit_up_enabled(mut self, enabled: bool) -> Self {
+        self.speed_limit_up_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the download speed limit in KB/s.
+    pub fn speed_limit_down(mut self, kbps: u32) -> Self {
+        self.speed_limit_down = Some(kbps);
+        self
+    }
+
+    /// Toggle limiting the download speed.
+    pub fn speed_limit_down_enabled(mut self, enabled: bool) -> Self {
+        self.speed_limit_down_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the alternative ("turtle mode") upload speed limit in KB/s.
+    pub fn alt_speed_up(mut self, kbps: u32) -> Self {
+        self.alt_speed_up = Some(kbps);
+        self
+    }
+
+    /// Set the alternative ("turtle mode") download speed limit in KB/s.
+    pub fn alt_speed_down(mut self, kbps: u32) -> Self {
+        self.alt_speed_down = Some(kbps);
+        self
+    }
+
+    /// Toggle using the alternative speed limits.
+    pub fn alt_speed_enabled(mut self, enabled: bool) -> Self {
+        self.alt_speed_enabled = Some(enabled);
+        self
+    }
+
+    /// Set when to automatically use the alternative speed limits.
+    pub fn alt_speed_schedule(mut self, schedule: AltSpeedSchedule) -> Self {
+        self.alt_speed_schedule = Some(schedule);
+        self
+    }
+
+    /// Toggle using the alternative speed schedule.
+    pub fn alt_speed_schedule_enabled(mut self, enabled: bool) -> Self {
+        self.alt_speed_schedule_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the seed ratio limit used by torrents in `RatioMode::Global`.
+    pub fn ratio_limit(mut self, ratio: f64) -> Self {
+        self.ratio_limit = Some(ratio);
+        self
+    }
+
+    /// Toggle limiting the seed ratio of torrents in `RatioMode::Global`.
+    pub fn ratio_limit_enabled(mut self, enabled: bool) -> Self {
+        self.ratio_limit_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the idle seeding limit in minutes used by torrents in `IdleMode::Global`.
+    pub fn idle_seeding_limit(mut self, minutes: u16) -> Self {
+        self.idle_seeding_limit = Some(minutes);
+        self
+    }
+
+    /// Toggle limiting the idle seeding time of torrents in `IdleMode::Global`.
+    pub fn idle_seeding_limit_enabled(mut self, enabled: bool) -> Self {
+        self.idle_seeding_limit_enabled = Some(enabled);
+        self
+    }
+
+    /// Set how many torrents can download at once.
+    pub fn download_queue_size(mut self, size: u32) -> Self {
+        self.download_queue_size = Some(size);
+        self
+    }
+
+    /// Toggle limiting the number of downloading torrents.
+    pub fn download_queue_enabled(mut self, enabled: bool) -> Self {
+        self.download_queue_enabled = Some(enabled);
+        self
+    }
+
+    /// Set how many torrents can seed at once.
+    pub fn seed_queue_size(mut self, size: u32) -> Self {
+        self.seed_queue_size = Some(size);
+        self
+    }
+
+    /// Toggle limiting the number of seeding torrents.
+    pub fn seed_queue_enabled(mut self, enabled: bool) -> Self {
+        self.seed_queue_enabled = Some(enabled);
+        self
+    }
+
+    /// Set the minutes without activity before a torrent is stalled.
+    pub fn queue_stalled_minutes(mut self, minutes: u32) -> Self {
+        self.queue_stalled_minutes = Some(minutes);
+        self
+    }
+
+    /// Toggle marking inactive torrents as stalled.
+    pub fn queue_stalled_enabled(mut self, enabled: bool) -> Self {
+        self.queue_stalled_enabled = Some(enabled);
+        self
+    }
+
+    /// Apply the set options to a running session
+    ///
+    /// The strings and directories are checked first so nothing is changed if one is invalid.
+    ///
+    /// # Safety
+    ///
+    /// `ses` must be a running session that is locked for writing.
+    pub(crate) unsafe fn apply(&self, ses: *mut transmission_sys::tr_session) -> TrResult<()> {
+        let download_dir = self.download_dir.as_deref().map(dir_string).transpose()?;
+        let incomplete_dir = self.incomplete_dir.as_deref().map(dir_string).transpose()?;
+        let script = self
+            .script_torrent_done_filename
+            .as_deref()
+            .map(path_string)
+            .transpose()?;
+        let blocklist_url = self
+            .blocklist_url
+            .as_deref()
+            .map(|url| CString::new(url).map_err(|_| Error::StringNul))
+            .transpose()?;
+
+        let up = transmission_sys::tr_direction::TR_CLIENT_TO_PEER;
+        let down = transmission_sys::tr_direction::TR_PEER_TO_CLIENT;
+        // The session keeps its own copies of the strings
+        if let Some(dir) = &download_dir {
+            transmission_sys::tr_sessionSetDownloadDir(ses, dir.as_ptr());
+        }
+        if let Some(dir) = &incomplete_dir {
+            transmission_sys::tr_sessionSetIncompleteDir(ses, dir.as_ptr());
+        }
+        if let Some(enabled) = self.incomplete_dir_enabled {
+            transmission_sys::tr_sessionSetIncompleteDirEnabled(ses, enabled);
+        }
+        if let Some(rename) = self.rename_partial_files {
+            transmission_sys::tr_sessionSetIncompleteFileNamingEnabled(ses, rename);
+        }
+        if let Some(mb) = self.cache_size_mb {
+            transmission_sys::tr_sessionSetCacheLimit_MB(ses, clamp(mb));
+        }
+        if let Some(start) = self.start_added_torrents {
+            transmission_sys::tr_sessionSetPaused(ses, !start);
+        }
+        if let Some(trash) = self.trash_original_torrent_files {
+            transmission_sys::tr_sessionSetDeleteSource(ses, trash);
+        }
+        if let Some(script) = &script {
+            transmission_sys::tr_sessionSetTorrentDoneScript(ses, script.as_ptr());
+        }
+        if let Some(enabled) = self.script_torrent_done_enabled {
+            transmission_sys::tr_sessionSetTorrentDoneScriptEnabled(ses, enabled);
+        }
+
+        // Peers
+        if let Some(utp) = self.use_utp {
+            transmission_sys::tr_sessionSetUTPEnabled(ses, utp);
+        }
+        if let Some(port) = self.peer_port {
+            transmission_sys::tr_sessionSetPeerPort(ses, port);
+        }
+        if let Some(random) = self.peer_port_random_on_start {
+            transmission_sys::tr_sessionSetPeerPortRandomOnStart(ses, random);
+        }
+        if let Some(enabled) = self.port_forwarding_enabled {
+            transmission_sys::tr_sessionSetPortForwardingEnabled(ses, enabled);
+        }
+        if let Some(limit) = self.peer_limit_global {
+            transmission_sys::tr_sessionSetPeerLimit(ses, limit);
+        }
+        if let Some(limit) = self.peer_limit_per_torrent {
+            transmission_sys::tr_sessionSetPeerLimitPerTorrent(ses, limit);
+        }
+        if let Some(mode) = self.encryption {
+            transmission_sys::tr_sessionSetEncryption(ses, mode.into());
+        }
+        if let Some(enabled) = self.dht_enabled {
+            transmission_sys::tr_sessionSetDHTEnabled(ses, enabled);
+        }
+        if let Some(enabled) = self.pex_enabled {
+            transmission_sys::tr_sessionSetPexEnabled(ses, enabled);
+        }
+        if let Some(enabled) = self.lpd_enabled {
+            transmission_sys::tr_sessionSetLPDEnabled(ses, enabled);
+        }
+        if let Some(enabled) = self.blocklist_enabled {
+            transmission_sys::tr_blocklistSetEnabled(ses, enabled);
+        }
+        if let Some(url) = &blocklist_url {
+            transmission_sys::tr_blocklistSetURL(ses, url.as_ptr());
+        }
+
+        // Speed limits
+        if let Some(kbps) = self.speed_limit_up {
+            transmission_sys::tr_sessionSetSpeedLimit_KBps(ses, up, kbps);
+        }
+        if let Some(enabled) = self.speed_limit_up_enabled {
+            transmission_sys::tr_sessionLimitSpeed(ses, up, enabled);
+        }
+        if let Some(kbps) = self.speed_limit_down {
+            transmission_sys::tr_sessionSetSpeedLimit_KBps(ses, down, kbps);
+        }
+        if let Some(enabled) = self.speed_limit_down_enabled {
+            transmission_sys::tr_sessionLimitSpeed(ses, down, enabled);
+        }
+        if let Some(kbps) = self.alt_speed_up {
+            transmission_sys::tr_sessionSetAltSpeed_KBps(ses, up, kbps);
+        }
+        if let Some(kbps) = self.alt_speed_down {
+            transmission_sys::tr_sessionSetAltSpeed_KBps(ses, down, kbps);
+        }
+        if let Some(enabled) = self.alt_speed_enabled {
+            transmission_sys::tr_sessionUseAltSpeed(ses, enabled);
+        }
+        if let Some(schedule) = &self.alt_speed_schedule {
+            tr_sessionSetAltSpeedDayMask(ses, schedule.day_mask());
+            transmission_sys::tr_sessionSetAltSpeedBegin(ses, schedule.begin_minutes());
+            transmission_sys::tr_sessionSetAltSpeedEnd(ses, schedule.end_minutes());
+        }
+        if let Some(enabled) = self.alt_speed_schedule_enabled {
+            transmission_sys::tr_sessionUseAltSpeedTime(ses, enabled);
+        }
+
+        // Seeding limits
+        if let Some(ratio) = self.ratio_limit {
+            transmission_sys::tr_sessionSetRatioLimit(ses, ratio);
+        }
+        if let Some(enabled) = self.ratio_limit_enabled {
+            transmission_sys::tr_sessionSetRatioLimited(ses, enabled);
+        }
+        if let Some(minutes) = self.idle_seeding_limit {
+            transmission_sys::tr_sessionSetIdleLimit(ses, minutes);
+        }
+        if let Some(enabled) = self.idle_seeding_limit_enabled {
+            transmission_sys::tr_sessionSetIdleLimited(ses, enabled);
+        }
+
+        // Queues
+        if let Some(size) = self.download_queue_size {
+            transmission_sys::tr_sessionSetQueueSize(ses, down, clamp(size));
+        }
+        if let Some(enabled) = self.download_queue_enabled {
+            transmission_sys::tr_sessionSetQueueEnabled(ses, down, enabled);
+        }
+        if let Some(size) = self.seed_queue_size {
+            transmission_sys::tr_sessionSetQueueSize(ses, up, clamp(size));
+        }
+        if let Some(enabled) = self.seed_queue_enabled {
+            transmission_sys::tr_sessionSetQueueEnabled(ses, up, enabled);
+        }
+        if let Some(minutes) = self.queue_stalled_minutes {
+            transmission_sys::tr_sessionSetQueueStalledMinutes(ses, clamp(minutes));
+        }
+        if let Some(enabled) = self.queue_stalled_enabled {
+            transmission_sys::tr_sessionSetQueueStalledEnabled(ses, enabled);
+        }
+        Ok(())
+    }
+}
+
+/// A size or duration for Transmission, which can't take values above `c_int::MAX`
+fn clamp(value: u32) -> c_int {
+    c_int::try_from(value).unwrap_or(c_int::MAX)
+}
+
+/// A path as a string for Transmission
+fn path_string(path: &Path) -> TrResult<CString> {
+    let path = path.to_str().ok_or(Error::PathNotUtf8)?;
+    CString::new(path).map_err(|_| Error::StringNul)
+}
+
+/// The full path of a directory that has to exist as a string for Transmission
+fn dir_string(dir: &Path) -> TrResult<CString> {
+    path_string(&existing_dir(dir)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr::null_mut;
+
+    // These fail before the session is touched, so no session is needed
+
+    #[test]
+    fn invalid_path() {
+        let patch = ClientConfigPatch::new()
+            .peer_port(51414)
+            .script_torrent_done_filename("/tmp/do\0ne.sh");
+        assert_eq!(unsafe { patch.apply(null_mut()) }, Err(Error::StringNul));
+    }
+
+    #[test]
+    fn missing_dir() {
+        let patch = ClientConfigPatch::new()
+            .peer_port(51414)
+            .incomplete_dir("/tmp/tr-test-missing-dir");
+        assert_eq!(unsafe { patch.apply(null_mut()) }, Err(Error::DirNotFound));
+    }
+
+    #[test]
+    fn invalid_blocklist_url() {
+        let patch = ClientConfigPatch::new().blocklist_url("http://example.com/\0");
+        assert_eq!(unsafe { patch.apply(null_mut()) }, Err(Error::StringNul));
+    }
+}
@@ -1,5 +1,15 @@
+mod addtorrentoptions;
+mod altspeedschedule;
 mod client;
 mod clientconfig;
+mod clientconfigpatch;
+mod sessionevents;
+mod sessionstats;
 
+pub use addtorrentoptions::AddTorrentOptions;
+pub use altspeedschedule::AltSpeedSchedule;
 pub use client::Client;
-pub use clientconfig::ClientConfig;
+pub use clientconfig::{ClientConfig, Encryption, Preallocation};
+pub use clientconfigpatch::ClientConfigPatch;
+pub use sessionevents::{SessionEvent, Subscription};
+pub use sessionstats::SessionStats;
@@ -0,0 +1,343 @@
+//! Contains the SessionEvent enum and the watcher that produces them.
+use std::collections::BTreeMap;
+use std::mem;
+use std::ops::Deref;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use transmission_sys;
+
+use super::client::session_torrents;
+use crate::error::Error;
+use crate::torrent::{
+    hold_torrents, unlisten, Completeness, MoveState, Torrent, TorrentEvent, TorrentState,
+};
+
+/// How often the watcher looks for changes in the session's torrents
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Something that happened to one of the torrents of a session.
+///
+/// Received from `Client::subscribe()`.
+/// The torrents are identified by their ID, see `Client::torrent_by_id()`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SessionEvent {
+    /// A torrent was added to the session
+    Added { id: i32 },
+    /// A torrent was removed from the session
+    Removed { id: i32 },
+    /// A torrent was started
+    Started { id: i32 },
+    /// A torrent was stopped
+    Stopped { id: i32 },
+    /// All of the wanted files of a torrent were downloaded
+    Completed { id: i32 },
+    /// A torrent had a local error
+    Errored {
+        id: i32,
+        error: Error,
+        message: String,
+    },
+    /// The state of a torrent changed
+    StateChanged {
+        id: i32,
+        old: TorrentState,
+        new: TorrentState,
+    },
+    /// A tracker of a torrent returned an error
+    TrackerError { id: i32, message: String },
+    /// Moving the data of a torrent is done
+    Moved {
+        id: i32,
+        location: String,
+        state: MoveState,
+    },
+    /// A file or folder of a torrent was renamed
+    Renamed {
+        id: i32,
+        old_path: String,
+        new_name: String,
+    },
+}
+
+/// The events of a session, returned by `Client::subscribe()`.
+///
+/// Derefs to the `Receiver` of the events.
+/// The session stops looking for events once every `Subscription` is dropped.
+pub struct Subscription {
+    events: Receiver<SessionEvent>,
+    id: usize,
+    bus: Weak<EventBus>,
+}
+
+impl Deref for Subscription {
+    type Target = Receiver<SessionEvent>;
+
+    fn deref(&self) -> &Receiver<SessionEvent> {
+        &self.events
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Some(bus) = self.bus.upgrade() {
+            bus.unsubscribe(self.id);
+        }
+    }
+}
+
+/// A running watcher thread
+struct Watcher {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl Watcher {
+    /// Stop the thread and wait for it to finish
+    fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.thread.thread().unpark();
+        let _ = self.thread.join();
+    }
+}
+
+/// The session shared by the clones of a `Client`
+type Session = RwLock<NonNull<transmission_sys::tr_session>>;
+
+/// Sends the events of a session to its subscribers.
+///
+/// A watcher thread runs while there are subscribers. It polls the session
+/// for added and removed torrents and state changes, and forwards the callback
+/// based events of each torrent.
+pub(crate) struct EventBus {
+    /// The session of the `Client`, locked for each poll
+    session: Weak<Session>,
+    /// Held by the watcher while it has the session, see `EventBus::is_last()`
+    poll: Mutex<()>,
+    subscribers: Mutex<Vec<(usize, Sender<SessionEvent>)>>,
+    next_id: AtomicUsize,
+    watcher: Mutex<Option<Watcher>>,
+    /// The stop flag of the running watcher, also given to the listeners it adds
+    running: Mutex<Option<Arc<AtomicBool>>>,
+    /// The address and listener token of each forwarded torrent by ID
+    forwarded: Mutex<BTreeMap<i32, (usize, usize)>>,
+}
+
+impl EventBus {
+    pub(crate) fn new(session: &Arc<Session>) -> Self {
+        Self {
+            session: Arc::downgrade(session),
+            poll: Mutex::new(()),
+            subscribers: Mutex::new(Vec::new()),
+            next_id: AtomicUsize::new(0),
+            watcher: Mutex::new(None),
+            running: Mutex::new(None),
+            forwarded: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Is `session` only held by one `Client`?
+    ///
+    /// The watcher only holds the session while polling, which this waits for.
+    pub(crate) fn is_last(&self, session: &Arc<Session>) -> bool {
+        let _poll = self.poll.lock().unwrap();
+        Arc::strong_count(session) == 1
+    }
+
+    /// Add a subscriber, starting the watcher if needed
+    pub(crate) fn subscribe(self: &Arc<Self>) -> Subscription {
+        let (tx, rx) = channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        // Locked first so the watcher is not stopped by a subscriber leaving meanwhile
+        let mut watcher = self.watcher.lock().unwrap();
+        self.subscribers.lock().unwrap().push((id, tx));
+        if watcher.is_none() {
+            let bus = Arc::clone(self);
+            let stop = Arc::new(AtomicBool::new(false));
+            let stopped = Arc::clone(&stop);
+            *self.running.lock().unwrap() = Some(Arc::clone(&stop));
+            *watcher = Some(Watcher {
+                stop,
+                thread: thread::spawn(move || bus.watch(stopped)),
+            });
+        }
+        Subscription {
+            events: rx,
+            id,
+            bus: Arc::downgrade(self),
+        }
+    }
+
+    /// Remove a subscriber, stopping the watcher if it was the last one
+    fn unsubscribe(&self, id: usize) {
+        let mut watcher = self.watcher.lock().unwrap();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|(s, _)| *s != id);
+        if subscribers.is_empty() {
+            // The watcher sends to the subscribers so they must be unlocked first
+            drop(subscribers);
+            if let Some(watcher) = watcher.take() {
+                self.stop_watcher(watcher);
+            }
+        }
+    }
+
+    /// Stop the watcher and wait for it to finish
+    ///
+    /// Must be called before the session is closed.
+    pub(crate) fn stop(&self) {
+        if let Some(watcher) = self.watcher.lock().unwrap().take() {
+            self.stop_watcher(watcher);
+        }
+    }
+
+    /// Stop a watcher and remove the listeners it added
+    fn stop_watcher(&self, watcher: Watcher) {
+        self.running.lock().unwrap().take();
+        watcher.stop();
+        let forwarded = mem::take(&mut *self.forwarded.lock().unwrap());
+        for (tor, token) in forwarded.values() {
+            unlisten(*tor as *mut transmission_sys::tr_torrent, *token);
+        }
+    }
+
+    /// Forward the events of a torrent added through the `Client` right away
+    ///
+    /// Otherwise events before the next poll, like a quick completion, would be missed.
+    pub(crate) fn track(self: &Arc<Self>, torrent: &Torrent) {
+        // Kept locked so the watcher can't be stopped before the listener is recorded
+        let running = self.running.lock().unwrap();
+        if let Some(stop) = running.as_ref() {
+            self.forward(torrent, torrent.id(), stop);
+        }
+    }
+
+    /// Send an event to every subscriber, dropping the ones that are gone
+    fn send(&self, event: SessionEvent) {
+        self.subscribers
+            .lock()
+            .unwrap()
+            .retain(|(_, s)| s.send(event.clone()).is_ok());
+    }
+
+    /// Poll the session for changes until stopped
+    fn watch(self: Arc<Self>, stop: Arc<AtomicBool>) {
+        // The state and error of each torrent by ID as of the last poll
+        let mut known: BTreeMap<i32, (TorrentState, Error)> = BTreeMap::new();
+        let mut first = true;
+        while !stop.load(Ordering::SeqCst) {
+            let mut seen = BTreeMap::new();
+            {
+                let _poll = self.poll.lock().unwrap();
+                let session = match self.session.upgrade() {
+                    Some(session) => session,
+                    None => return,
+                };
+                let ses = session.read().unwrap();
+                // The torrents can't be removed until they are all polled
+                let _hold = hold_torrents();
+                for torrent in unsafe { session_torrents(ses.as_ptr()) } {
+                    let stats = torrent.stats();
+                    let id = stats.id;
+                    match known.remove(&id) {
+                        Some((state, error)) => {
+                            if state != stats.state {
+                                self.send(SessionEvent::StateChanged {
+                                    id,
+                                    old: state,
+                                    new: stats.state,
+                                });
+                                if stats.state == TorrentState::Stopped {
+                                    self.send(SessionEvent::Stopped { id });
+                                } else if state == TorrentState::Stopped {
+                                    self.send(SessionEvent::Started { id });
+                                }
+                            }
+                            if error != stats.error {
+                                self.send_error(id, &stats.error, &stats.error_string);
+                            }
+                        }
+                        // Torrents already in the session when the watcher starts are not new
+                        None => {
+                            if !first {
+                                self.send(SessionEvent::Added { id });
+                            }
+                            self.forward(&torrent, id, &stop);
+                        }
+                    }
+                    seen.insert(id, (stats.state, stats.error));
+                }
+            }
+            for id in known.keys() {
+                // The torrent's listeners were dropped when it was removed
+                self.forwarded.lock().unwrap().remove(id);
+                self.send(SessionEvent::Removed { id: *id });
+            }
+            known = seen;
+            first = false;
+            thread::park_timeout(POLL_INTERVAL);
+        }
+    }
+
+    /// Send the event for a torrent's new error
+    fn send_error(&self, id: i32, error: &Error, message: &str) {
+        match error {
+            Error::StatTracker => self.send(SessionEvent::TrackerError {
+                id,
+                message: String::from(message),
+            }),
+            Error::StatLocal => self.send(SessionEvent::Errored {
+                id,
+                error: error.clone(),
+                message: String::from(message),
+            }),
+            _ => (),
+        }
+    }
+
+    /// Forward the callback based events of a torrent until the watcher is stopped
+    ///
+    /// The listener is only called for the torrent with `id`, even if its address is reused.
+    /// Torrents that are already forwarded are skipped.
+    fn forward(self: &Arc<Self>, torrent: &Torrent, id: i32, stop: &Arc<AtomicBool>) {
+        let mut forwarded = self.forwarded.lock().unwrap();
+        if forwarded.contains_key(&id) {
+            return;
+        }
+        let bus = Arc::downgrade(self);
+        let stop = Arc::clone(stop);
+        let listening = torrent.listen(Box::new(move |event| {
+            let bus = match Weak::upgrade(&bus) {
+                Some(bus) if !stop.load(Ordering::SeqCst) => bus,
+                _ => return false,
+            };
+            let event = match event {
+                TorrentEvent::Completeness(Completeness::Leech) => return true,
+                TorrentEvent::Completeness(_) => SessionEvent::Completed { id },
+                TorrentEvent::Renamed { old_path, new_name } => SessionEvent::Renamed {
+                    id,
+                    old_path: old_path.clone(),
+                    new_name: new_name.clone(),
+                },
+                TorrentEvent::Moved { location, state } => SessionEvent::Moved {
+                    id,
+                    location: location.clone(),
+                    state: *state,
+                },
+                _ => return true,
+            };
+            bus.send(event);
+            true
+        }));
+        forwarded.insert(id, listening);
+    }
+}
+
+// The session is only used under its lock, like in `Client`
+unsafe impl std::marker::Send for EventBus {}
+unsafe impl std::marker::Sync for EventBus {}
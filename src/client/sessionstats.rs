@@ -0,0 +1,48 @@
+//! Contains the SessionStats struct.
+use serde::{Deserialize, Serialize};
+use transmission_sys;
+
+/// The stats of a whole session.
+///
+/// You can get them with `Client::session_stats()` for the current session
+/// or `Client::cumulative_stats()` for all sessions combined.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionStats {
+    /// Total bytes uploaded.
+    pub uploaded_bytes: u64,
+    /// Total bytes downloaded.
+    pub downloaded_bytes: u64,
+    /// Number of files added.
+    pub files_added: u64,
+    /// Number of times the session has been started.
+    pub session_count: u64,
+    /// How long the session has been active in seconds.
+    pub seconds_active: u64,
+    /// Upload/download ratio.
+    /// -1 if not available and -2 if infinite.
+    pub ratio: f32,
+    /// The current raw upload speed of all torrents.
+    pub raw_upload_speed_kbps: f64,
+    /// The current raw download speed of all torrents.
+    pub raw_download_speed_kbps: f64,
+}
+
+impl SessionStats {
+    /// Combines the stats from Transmission with the current session speeds
+    pub(crate) fn new(
+        stats: transmission_sys::tr_session_stats,
+        raw_upload_speed_kbps: f64,
+        raw_download_speed_kbps: f64,
+    ) -> Self {
+        Self {
+            uploaded_bytes: stats.uploadedBytes,
+            downloaded_bytes: stats.downloadedBytes,
+            files_added: stats.filesAdded,
+            session_count: stats.sessionCount,
+            seconds_active: stats.secondsActive,
+            ratio: stats.ratio,
+            raw_upload_speed_kbps,
+            raw_download_speed_kbps,
+        }
+    }
+}
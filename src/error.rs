@@ -10,7 +10,7 @@ use transmission_sys;
 ///
 /// This enum acts as a general wrapper for errors. Most errors produced by
 /// `transmission-sys` can be converted to this using `Error::From`.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Error {
     /// A general state of non-error.
     /// If this is is ever the `Err` of a `Result` please file a bug report.
@@ -33,6 +33,42 @@ pub enum Error {
     MakeMetaUrl,
     /// Getting metainfo was cancelled.
     MakeMetaCancelled,
+    /// A tracker URL is not a valid announce URL.
+    TrackerUrl,
+    /// The tracker is not one of the torrent's trackers.
+    TrackerNotFound,
+    /// The path to rename is not in the torrent or the new name is invalid.
+    RenameInvalid,
+    /// A file index is not one of the torrent's files.
+    FileIndexInvalid,
+    /// A path is not valid UTF-8.
+    PathNotUtf8,
+    /// A string contains a NUL byte, which Transmission can't take.
+    StringNul,
+    /// A magnet link could not be parsed.
+    MagnetInvalid,
+    /// Torrent metainfo is not valid bencode.
+    Bencode,
+    /// The torrent was removed while waiting on it.
+    TorrentRemoved,
+    /// Verifying the torrent was aborted before it finished.
+    VerifyAborted,
+    /// None of the torrent's downloaded data was found on disk.
+    DataMissing,
+    /// A settings file is not valid JSON.
+    SettingsInvalid,
+    /// The application name of a `ClientConfig` was not set.
+    AppNameMissing,
+    /// The configuration directory of a `ClientConfig` was not set.
+    ConfigDirMissing,
+    /// The download directory of a `ClientConfig` was not set.
+    DownloadDirMissing,
+    /// A directory does not exist.
+    DirNotFound,
+    /// A port is not a number from 0 to 65535.
+    PortInvalid,
+    /// Transmission could not start the session.
+    SessionInit,
 }
 
 impl fmt::Display for Error {
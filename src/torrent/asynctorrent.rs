@@ -0,0 +1,155 @@
+//! Async versions of the waiting parts of `Torrent`, enabled with the `async` feature.
+//!
+//! These are runtime agnostic and built on the torrent's event callbacks,
+//! so they work the same under tokio, async-std or a simple executor.
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_channel::oneshot;
+use futures_core::{ready, Stream};
+use futures_timer::Delay;
+use transmission_sys;
+
+use super::events::{TorrentEvent, Watch};
+use super::{Completeness, Torrent, TorrentStats, VerifyResult};
+use crate::error::{Error, TrResult};
+
+impl Torrent {
+    /// Wait until all of the wanted files of the torrent are downloaded
+    ///
+    /// Fails with `Error::TorrentRemoved` if the torrent is removed first.
+    pub async fn wait_complete(&self) -> TrResult<()> {
+        let (tx, rx) = oneshot::channel();
+        let mut tx = Some(tx);
+        // Listen before checking so completing in between is not missed
+        self.listen(Box::new(move |event| match event {
+            TorrentEvent::Completeness(Completeness::Leech) => true,
+            TorrentEvent::Completeness(_) => {
+                if let Some(tx) = tx.take() {
+                    let _ = tx.send(());
+                }
+                false
+            }
+            _ => matches!(&tx, Some(tx) if !tx.is_canceled()),
+        }));
+        if self.stats().percent_done >= 1.0 {
+            return Ok(());
+        }
+        rx.await.map_err(|_| Error::TorrentRemoved)
+    }
+
+    /// Wait until the metadata of a torrent added by magnet link is received
+    ///
+    /// Fails with `Error::TorrentRemoved` if the torrent is removed first.
+    pub async fn wait_metadata(&self) -> TrResult<()> {
+        let (tx, rx) = oneshot::channel();
+        let mut tx = Some(tx);
+        self.listen(Box::new(move |event| match event {
+            TorrentEvent::Metadata => {
+                if let Some(tx) = tx.take() {
+                    let _ = tx.send(());
+                }
+                false
+            }
+            _ => matches!(&tx, Some(tx) if !tx.is_canceled()),
+        }));
+        if self.stats().metadata_percent_complete >= 1.0 {
+            return Ok(());
+        }
+        rx.await.map_err(|_| Error::TorrentRemoved)
+    }
+
+    /// Verify the torrent and wait until it's done
+    ///
+    /// Fails with `Error::VerifyAborted` if verifying was stopped before it finished,
+    /// or the torrent was removed or the session closed first.
+    /// Fails with `Error::DataMissing` if none of the torrent's data is on disk anymore.
+    pub async fn verify_async(&self) -> TrResult<VerifyResult> {
+        let (tx, rx) = oneshot::channel();
+        // Called exactly once, even if the torrent is removed
+        self.verify_with(move |result| {
+            let _ = tx.send(result);
+        })?;
+        match rx.await {
+            Ok(result) if !result.aborted => Ok(result),
+            _ => Err(Error::VerifyAborted),
+        }
+    }
+
+    /// A `Stream` of the torrent's stats taken every `interval`
+    ///
+    /// The first stats are taken right away.
+    /// The stream ends when the torrent is removed or the session is closed.
+    pub fn stats_stream(&self, interval: Duration) -> impl Stream<Item = TorrentStats> {
+        StatsStream {
+            watch: self.watch(),
+            interval,
+            delay: None,
+        }
+    }
+}
+
+/// The stream returned by `Torrent::stats_stream()`
+struct StatsStream {
+    watch: Watch,
+    interval: Duration,
+    /// Waits for the next stats, `None` before the first ones
+    delay: Option<Delay>,
+}
+
+impl Stream for StatsStream {
+    type Item = TorrentStats;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<TorrentStats>> {
+        if let Some(delay) = self.delay.as_mut() {
+            ready!(Pin::new(delay).poll(cx));
+        }
+        self.delay = Some(Delay::new(self.interval));
+        Poll::Ready(
+            self.watch.with(|tor| unsafe {
+                TorrentStats::from(transmission_sys::tr_torrentStatCached(tor))
+            }),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, ClientConfig, TorrentBuilder};
+    use futures_executor::block_on;
+    use std::fs;
+
+    #[test]
+    fn verify_missing_data() {
+        let test_dir = "/tmp/tr-test-verify";
+        let data = "/tmp/tr-test-verify/data.bin";
+
+        fs::create_dir(test_dir).unwrap();
+        fs::write(data, vec![7; 64 * 1024]).unwrap();
+        let torrent_file = TorrentBuilder::new()
+            .set_file(data)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let c = ClientConfig::new()
+            .app_name("testing")
+            .config_dir(test_dir)
+            .download_dir(test_dir);
+        let client = Client::new(c);
+        let t = client
+            .add_torrent_file(&torrent_file)
+            .unwrap()
+            .into_torrent();
+
+        assert_eq!(block_on(t.verify_async()).unwrap().failed_pieces, 0);
+        fs::remove_file(data).unwrap();
+        assert_eq!(block_on(t.verify_async()).err(), Some(Error::DataMissing));
+
+        client.close();
+        fs::remove_dir_all(test_dir).unwrap_or(());
+    }
+}
@@ -0,0 +1,145 @@
+//! Contains the DataMove handle for following a move of a torrent's data.
+use std::cell::UnsafeCell;
+use std::mem;
+use std::os::raw::c_int;
+use std::ptr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use transmission_sys;
+
+use super::events::Watch;
+
+/// How often the watcher thread checks if a move is done
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The state of a move started with `Torrent::move_data()`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum MoveState {
+    /// The data is still being moved
+    Moving,
+    /// The data was moved successfully
+    Done,
+    /// Moving the data failed, or the torrent was removed or its session closed first
+    Error,
+}
+
+impl From<c_int> for MoveState {
+    fn from(state: c_int) -> Self {
+        match state {
+            x if x == transmission_sys::TR_LOC_DONE as c_int => MoveState::Done,
+            x if x == transmission_sys::TR_LOC_ERROR as c_int => MoveState::Error,
+            _ => MoveState::Moving,
+        }
+    }
+}
+
+/// Where Transmission writes the progress of a move from its event thread
+struct MoveStatus {
+    progress: UnsafeCell<f64>,
+    state: UnsafeCell<c_int>,
+}
+
+// Both fields are only ever accessed with volatile reads and writes
+unsafe impl std::marker::Send for MoveStatus {}
+unsafe impl std::marker::Sync for MoveStatus {}
+
+/// A handle to a move of a torrent's data.
+///
+/// Returned by `Torrent::move_data()` and can be polled for the progress and state
+/// of the move. Dropping it does not cancel the move.
+#[derive(Clone)]
+pub struct DataMove {
+    status: Arc<MoveStatus>,
+}
+
+impl DataMove {
+    /// Create the status for a move that is about to start
+    pub(crate) fn new() -> Self {
+        Self {
+            status: Arc::new(MoveStatus {
+                progress: UnsafeCell::new(0.0),
+                state: UnsafeCell::new(transmission_sys::TR_LOC_MOVING as c_int),
+            }),
+        }
+    }
+
+    /// Pointer Transmission writes the progress to
+    pub(crate) fn progress_ptr(&self) -> *mut f64 {
+        self.status.progress.get()
+    }
+
+    /// Pointer Transmission writes the state to
+    pub(crate) fn state_ptr(&self) -> *mut c_int {
+        self.status.state.get()
+    }
+
+    /// Keep the status alive until the move is done then call `callback` with the final state
+    ///
+    /// Transmission writes to the status until the move is done so this
+    /// has to be called for every move even if there is no callback.
+    /// If the torrent of `watch` is forgotten first the move never finishes,
+    /// so it is reported as `MoveState::Error`.
+    pub(crate) fn watch<F>(&self, watch: Watch, callback: F)
+    where
+        F: FnOnce(MoveState) + Send + 'static,
+    {
+        let handle = self.clone();
+        thread::spawn(move || loop {
+            match handle.state() {
+                MoveState::Moving if watch.with(|_| ()).is_none() => {
+                    unsafe {
+                        ptr::write_volatile(
+                            handle.state_ptr(),
+                            transmission_sys::TR_LOC_ERROR as c_int,
+                        );
+                    }
+                    // Transmission could still write to the status so it is never freed
+                    mem::forget(handle);
+                    return callback(MoveState::Error);
+                }
+                MoveState::Moving => thread::sleep(POLL_INTERVAL),
+                state => return callback(state),
+            }
+        });
+    }
+
+    /// Progress of the move from 0.0 to 1.0
+    pub fn progress(&self) -> f64 {
+        unsafe { ptr::read_volatile(self.status.progress.get()) }
+    }
+
+    /// The current state of the move
+    pub fn state(&self) -> MoveState {
+        MoveState::from(unsafe { ptr::read_volatile(self.status.state.get()) })
+    }
+
+    /// Is the move done, successfully or not?
+    pub fn is_finished(&self) -> bool {
+        self.state() != MoveState::Moving
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::torrent::forget_events;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn forgotten_move() {
+        // Nothing calls libtransmission so a made up torrent is enough
+        let tor = 0x40 as *mut transmission_sys::tr_torrent;
+        let data_move = DataMove::new();
+        let (tx, rx) = channel();
+        data_move.watch(Watch::new(tor), move |state| tx.send(state).unwrap());
+        forget_events(tor);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)),
+            Ok(MoveState::Error)
+        );
+        assert!(data_move.is_finished());
+    }
+}
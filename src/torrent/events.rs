@@ -0,0 +1,343 @@
+//! Dispatching of libtransmission's per-torrent callbacks to Rust closures.
+//!
+//! libtransmission only keeps one callback of each kind per torrent, and several
+//! `Torrent` handles can point to the same torrent, so the closures are kept here
+//! keyed by the torrent's address and ID instead of in the handles.
+//! The C callbacks are always the same functions and look the listeners up.
+//!
+//! One-shot callbacks, like the ones of a rename, are kept here as well since
+//! libtransmission does not call them if their torrent is removed first.
+//!
+//! The closures are called from libtransmission's threads, so a panic in one is
+//! caught instead of unwinding into C. They can also be called while a `Torrent`
+//! method of the same torrent holds the handle's lock, for example the completeness
+//! changes when `Torrent::set_files_download()` is called, so closures must not use
+//! a clone of the handle they were registered on.
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::mem;
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock, RwLockReadGuard};
+
+use serde::{Deserialize, Serialize};
+use transmission_sys;
+
+use super::MoveState;
+
+/// How much of a torrent is downloaded.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Completeness {
+    /// The torrent is still downloading
+    Leech,
+    /// The whole torrent is downloaded
+    Seed,
+    /// All of the wanted files are downloaded but some files are not wanted
+    PartialSeed,
+}
+
+impl From<transmission_sys::tr_completeness> for Completeness {
+    fn from(completeness: transmission_sys::tr_completeness) -> Self {
+        match completeness {
+            transmission_sys::tr_completeness::TR_LEECH => Completeness::Leech,
+            transmission_sys::tr_completeness::TR_SEED => Completeness::Seed,
+            transmission_sys::tr_completeness::TR_PARTIAL_SEED => Completeness::PartialSeed,
+        }
+    }
+}
+
+/// Something that happened to a torrent.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TorrentEvent {
+    /// The completeness changed
+    Completeness(Completeness),
+    /// The metadata of a magnet link was received
+    Metadata,
+    /// The seed ratio limit was reached
+    RatioLimit,
+    /// The idle seeding limit was reached
+    IdleLimit,
+    /// A file or folder was renamed with `Torrent::rename_path()`
+    Renamed { old_path: String, new_name: String },
+    /// A move started with `Torrent::move_data()` is done
+    Moved { location: String, state: MoveState },
+}
+
+/// Called with every event of a torrent, is dropped once it returns false
+pub(crate) type Listener = Box<dyn FnMut(&TorrentEvent) -> bool + Send>;
+
+/// A listener with its token and the ID of its torrent
+type Listening = (usize, i32, Listener);
+
+/// The listeners of each torrent by its address
+static LISTENERS: Mutex<BTreeMap<usize, Vec<Listening>>> = Mutex::new(BTreeMap::new());
+
+/// The data of a one-shot callback with the address of its torrent
+type Pending = (usize, Box<dyn Any + Send>);
+
+/// The pending one-shot callbacks by their token
+static PENDING: Mutex<BTreeMap<usize, Pending>> = Mutex::new(BTreeMap::new());
+
+/// The token of the next pending callback
+static NEXT_TOKEN: AtomicUsize = AtomicUsize::new(1);
+
+/// A callback that is called exactly once, with `fallback()` if it is dropped first
+pub(crate) struct OnceCallback<T> {
+    callback: Option<Box<dyn FnOnce(T) + Send>>,
+    fallback: fn() -> T,
+}
+
+impl<T> OnceCallback<T> {
+    pub(crate) fn new<F>(callback: F, fallback: fn() -> T) -> Self
+    where
+        F: FnOnce(T) + Send + 'static,
+    {
+        Self {
+            callback: Some(Box::new(callback)),
+            fallback,
+        }
+    }
+
+    /// Call the callback with `value`
+    pub(crate) fn call(mut self, value: T) {
+        if let Some(callback) = self.callback.take() {
+            let _ = panic::catch_unwind(AssertUnwindSafe(move || callback(value)));
+        }
+    }
+}
+
+impl<T> Drop for OnceCallback<T> {
+    fn drop(&mut self) {
+        if let Some(callback) = self.callback.take() {
+            let value = (self.fallback)();
+            let _ = panic::catch_unwind(AssertUnwindSafe(move || callback(value)));
+        }
+    }
+}
+
+/// The address of the torrent of each `Watch` by its token, also locked by `hold()`
+static WATCHES: RwLock<BTreeMap<usize, usize>> = RwLock::new(BTreeMap::new());
+
+/// Use of a torrent from outside of its handles, which ends when the torrent is forgotten.
+///
+/// Handles kept in the background would keep `Torrent::remove()` from working,
+/// and would not notice when the torrent is removed through another handle.
+pub(crate) struct Watch {
+    token: usize,
+    tor: usize,
+}
+
+impl Watch {
+    pub(crate) fn new(tor: *mut transmission_sys::tr_torrent) -> Self {
+        let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+        WATCHES.write().unwrap().insert(token, tor as usize);
+        Self {
+            token,
+            tor: tor as usize,
+        }
+    }
+
+    /// Call `f` with the torrent unless it was forgotten
+    ///
+    /// The torrent can't be forgotten, and so removed, until `f` returns.
+    pub(crate) fn with<F, T>(&self, f: F) -> Option<T>
+    where
+        F: FnOnce(*mut transmission_sys::tr_torrent) -> T,
+    {
+        let watches = WATCHES.read().unwrap();
+        if watches.contains_key(&self.token) {
+            Some(f(self.tor as *mut transmission_sys::tr_torrent))
+        } else {
+            None
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        WATCHES.write().unwrap().remove(&self.token);
+    }
+}
+
+/// Keep every torrent from being forgotten, and so removed, until the guard is dropped
+pub(crate) fn hold() -> RwLockReadGuard<'static, BTreeMap<usize, usize>> {
+    WATCHES.read().unwrap()
+}
+
+/// Keep the data of a one-shot callback of a torrent until `take_pending()`
+///
+/// The returned token is given to libtransmission instead of a pointer to the data,
+/// so a callback made after the torrent was forgotten finds nothing.
+pub(crate) fn pend<T>(tor: *mut transmission_sys::tr_torrent, data: T) -> usize
+where
+    T: Any + Send,
+{
+    let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    PENDING
+        .lock()
+        .unwrap()
+        .insert(token, (tor as usize, Box::new(data)));
+    token
+}
+
+/// Take back the data kept by `pend()`, `None` if its torrent was forgotten since
+pub(crate) fn take_pending<T: Any>(token: usize) -> Option<T> {
+    let (_, data) = PENDING.lock().unwrap().remove(&token)?;
+    data.downcast().ok().map(|data| *data)
+}
+
+/// Add a listener to a torrent's events, returns the token to remove it with `unlisten()`
+pub(crate) fn listen(tor: *mut transmission_sys::tr_torrent, listener: Listener) -> usize {
+    let id = unsafe { transmission_sys::tr_torrentId(tor) };
+    let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+    LISTENERS
+        .lock()
+        .unwrap()
+        .entry(tor as usize)
+        .or_default()
+        .push((token, id, listener));
+    // Setting the callbacks again is harmless so this is not tracked
+    unsafe {
+        transmission_sys::tr_torrentSetCompletenessCallback(tor, Some(on_completeness), null_mut());
+        transmission_sys::tr_torrentSetMetadataCallback(tor, Some(on_metadata), null_mut());
+        transmission_sys::tr_torrentSetRatioLimitHitCallback(tor, Some(on_ratio_limit), null_mut());
+        transmission_sys::tr_torrentSetIdleLimitHitCallback(tor, Some(on_idle_limit), null_mut());
+    }
+    token
+}
+
+/// Remove a listener added with `listen()`
+///
+/// Nothing happens if the torrent was forgotten, or if the listener is being called
+/// since the listeners are taken out while they are.
+pub(crate) fn unlisten(tor: *mut transmission_sys::tr_torrent, token: usize) {
+    if let Some(listeners) = LISTENERS.lock().unwrap().get_mut(&(tor as usize)) {
+        listeners.retain(|(t, _, _)| *t != token);
+    }
+}
+
+/// Unset the callbacks of a torrent and drop its listeners and pending callbacks
+///
+/// Must be called before the torrent is freed since the address can be reused.
+pub(crate) fn forget(tor: *mut transmission_sys::tr_torrent) {
+    // Dropped once unlocked since dropping a `OnceCallback` calls it
+    let pending: Vec<_> = {
+        let mut pending = PENDING.lock().unwrap();
+        let tokens: Vec<usize> = pending
+            .iter()
+            .filter(|(_, (t, _))| *t == tor as usize)
+            .map(|(token, _)| *token)
+            .collect();
+        tokens.iter().filter_map(|t| pending.remove(t)).collect()
+    };
+    drop(pending);
+    // Waits for any `Watch::with()` using the torrent to return
+    WATCHES
+        .write()
+        .unwrap()
+        .retain(|_, watched| *watched != tor as usize);
+    if LISTENERS.lock().unwrap().remove(&(tor as usize)).is_some() {
+        unsafe {
+            transmission_sys::tr_torrentClearCompletenessCallback(tor);
+            transmission_sys::tr_torrentSetMetadataCallback(tor, None, null_mut());
+            transmission_sys::tr_torrentClearRatioLimitHitCallback(tor);
+            transmission_sys::tr_torrentClearIdleLimitHitCallback(tor);
+        }
+    }
+}
+
+/// Call the listeners of the torrent with `id`
+///
+/// Listeners of an earlier torrent at the same address are skipped,
+/// since the torrent may be gone by the time an event is sent.
+pub(crate) fn dispatch(tor: *mut transmission_sys::tr_torrent, id: i32, event: TorrentEvent) {
+    // The listeners are taken out so they can add listeners without deadlocking
+    let mut listeners = match LISTENERS.lock().unwrap().get_mut(&(tor as usize)) {
+        Some(listeners) => mem::take(listeners),
+        None => return,
+    };
+    // A listener that panics is dropped
+    listeners.retain_mut(|(_, listener_id, listener)| {
+        *listener_id != id
+            || panic::catch_unwind(AssertUnwindSafe(|| listener(&event))).unwrap_or(false)
+    });
+    if let Some(added) = LISTENERS.lock().unwrap().get_mut(&(tor as usize)) {
+        listeners.append(added);
+        *added = listeners;
+    }
+}
+
+/// Call the listeners of a torrent from one of its callbacks
+pub(crate) unsafe fn dispatch_current(tor: *mut transmission_sys::tr_torrent, event: TorrentEvent) {
+    dispatch(tor, transmission_sys::tr_torrentId(tor), event);
+}
+
+unsafe extern "C" fn on_completeness(
+    tor: *mut transmission_sys::tr_torrent,
+    completeness: transmission_sys::tr_completeness,
+    _was_running: bool,
+    _user_data: *mut c_void,
+) {
+    dispatch_current(
+        tor,
+        TorrentEvent::Completeness(Completeness::from(completeness)),
+    );
+}
+
+unsafe extern "C" fn on_metadata(tor: *mut transmission_sys::tr_torrent, _user_data: *mut c_void) {
+    dispatch_current(tor, TorrentEvent::Metadata);
+}
+
+unsafe extern "C" fn on_ratio_limit(
+    tor: *mut transmission_sys::tr_torrent,
+    _user_data: *mut c_void,
+) {
+    dispatch_current(tor, TorrentEvent::RatioLimit);
+}
+
+unsafe extern "C" fn on_idle_limit(
+    tor: *mut transmission_sys::tr_torrent,
+    _user_data: *mut c_void,
+) {
+    dispatch_current(tor, TorrentEvent::IdleLimit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn forget_pending() {
+        // Never dereferenced since the torrent has no listeners
+        let tor = 0x10 as *mut transmission_sys::tr_torrent;
+        let (tx, rx) = channel();
+        let done = tx.clone();
+        let called = pend(tor, OnceCallback::new(move |v| tx.send(v).unwrap(), || 0));
+        let forgotten = pend(tor, OnceCallback::new(move |v| done.send(v).unwrap(), || 0));
+
+        take_pending::<OnceCallback<i32>>(called).unwrap().call(1);
+        forget(tor);
+        assert!(take_pending::<OnceCallback<i32>>(forgotten).is_none());
+        assert_eq!(rx.try_iter().collect::<Vec<_>>(), vec![1, 0]);
+    }
+
+    #[test]
+    fn forget_watch() {
+        let tor = 0x30 as *mut transmission_sys::tr_torrent;
+        let watch = Watch::new(tor);
+        assert_eq!(watch.with(|t| t as usize), Some(0x30));
+        forget(tor);
+        assert_eq!(watch.with(|t| t as usize), None);
+    }
+
+    #[test]
+    fn panicking_callback() {
+        let tor = 0x20 as *mut transmission_sys::tr_torrent;
+        let token = pend(tor, OnceCallback::new(|_: i32| panic!("in callback"), || 0));
+        take_pending::<OnceCallback<i32>>(token).unwrap().call(1);
+        drop(OnceCallback::new(|_: i32| panic!("in fallback"), || 0));
+    }
+}
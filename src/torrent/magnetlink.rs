@@ -0,0 +1,328 @@
+//! Contains the MagnetLink struct for parsing and building magnet URIs.
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, TrResult};
+
+/// Most file indices a `so` parameter can select, so a huge range can't exhaust memory
+const MAX_SELECT_ONLY: usize = 100_000;
+
+/// A BitTorrent magnet link.
+///
+/// Can be parsed from a `magnet:?xt=urn:btih:` URI, edited through its fields,
+/// and turned back into a URI with `to_string()`.
+///
+/// ```
+/// use transmission::torrent::MagnetLink;
+///
+/// let mut link: MagnetLink = "magnet:?xt=urn:btih:f04905751c91af11a3745b1ce4500f4bf0de0d18&dn=Example"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(link.name, Some(String::from("Example")));
+///
+/// link.trackers.push(String::from("udp://tracker.opentrackr.org:1337"));
+/// assert_eq!(
+///     link.to_string(),
+///     "magnet:?xt=urn:btih:f04905751c91af11a3745b1ce4500f4bf0de0d18&dn=Example\
+///      &tr=udp%3A%2F%2Ftracker.opentrackr.org%3A1337"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MagnetLink {
+    /// The info hash of the torrent
+    pub hash: [u8; 20],
+    /// Display name (`dn`)
+    pub name: Option<String>,
+    /// Tracker announce URLs (`tr`)
+    pub trackers: Vec<String>,
+    /// Webseed URLs (`ws`)
+    pub webseeds: Vec<String>,
+    /// Exact length of the torrent in bytes (`xl`), not used by Transmission
+    pub length: Option<u64>,
+    /// Indices of the only files to download (`so`), not honoured by `Client::add_magnet()`
+    pub select_only: Vec<u32>,
+}
+
+impl MagnetLink {
+    /// Create a new magnet link with only an info hash
+    pub fn new(hash: [u8; 20]) -> Self {
+        Self {
+            hash,
+            name: None,
+            trackers: Vec::new(),
+            webseeds: Vec::new(),
+            length: None,
+            select_only: Vec::new(),
+        }
+    }
+
+    /// Parse and validate a magnet URI
+    ///
+    /// The info hash can be either 40 hex or 32 base32 characters.
+    /// Parameters other than the ones in `MagnetLink` are ignored.
+    /// Fails with `Error::MagnetInvalid` if the URI is not a valid BitTorrent magnet link.
+    pub fn parse(uri: &str) -> TrResult<Self> {
+        let query = match uri.get(..8) {
+            Some(scheme) if scheme.eq_ignore_ascii_case("magnet:?") => &uri[8..],
+            _ => return Err(Error::MagnetInvalid),
+        };
+
+        let mut hash = None;
+        let mut link = Self::new([0; 20]);
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let mut kv = param.splitn(2, '=');
+            let key = kv.next().unwrap_or("");
+            let value = percent_decode(kv.next().ok_or(Error::MagnetInvalid)?)?;
+            // Numbered keys like tr.1 are allowed for repeated parameters
+            match key.split('.').next().unwrap_or("") {
+                "xt" if hash.is_none() => hash = hash_from_urn(&value),
+                "dn" => link.name = Some(value),
+                "tr" => link.trackers.push(value),
+                "ws" => link.webseeds.push(value),
+                "xl" => link.length = Some(value.parse().map_err(|_| Error::MagnetInvalid)?),
+                "so" => link.select_only = parse_select_only(&value)?,
+                _ => (),
+            }
+        }
+
+        link.hash = hash.ok_or(Error::MagnetInvalid)?;
+        Ok(link)
+    }
+
+    /// The info hash as a 40 character lowercase hex string
+    pub fn hash_string(&self) -> String {
+        self.hash.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl FromStr for MagnetLink {
+    type Err = Error;
+
+    fn from_str(s: &str) -> TrResult<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for MagnetLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "magnet:?xt=urn:btih:{}", self.hash_string())?;
+        if let Some(name) = &self.name {
+            write!(f, "&dn={}", percent_encode(name))?;
+        }
+        if let Some(length) = self.length {
+            write!(f, "&xl={}", length)?;
+        }
+        for tracker in &self.trackers {
+            write!(f, "&tr={}", percent_encode(tracker))?;
+        }
+        for webseed in &self.webseeds {
+            write!(f, "&ws={}", percent_encode(webseed))?;
+        }
+        if !self.select_only.is_empty() {
+            write!(f, "&so={}", format_select_only(&self.select_only))?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a 40 character hex info hash
+pub(crate) fn hash_from_hex(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 || !hex.is_ascii() {
+        return None;
+    }
+    let mut hash = [0; 20];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = hex_byte(&hex[i * 2..i * 2 + 2])?;
+    }
+    Some(hash)
+}
+
+/// Decodes two hex digits, unlike `u8::from_str_radix` a sign is not accepted
+fn hex_byte(hex: &str) -> Option<u8> {
+    if hex.len() != 2 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    u8::from_str_radix(hex, 16).ok()
+}
+
+/// Decodes a 32 character base32 info hash
+fn hash_from_base32(base32: &str) -> Option<[u8; 20]> {
+    if base32.len() != 32 {
+        return None;
+    }
+    let mut hash = [0; 20];
+    let mut bits: u64 = 0;
+    let mut bit_count = 0;
+    let mut i = 0;
+    for c in base32.bytes() {
+        let value = match c.to_ascii_uppercase() {
+            c @ b'A'..=b'Z' => c - b'A',
+            c @ b'2'..=b'7' => c - b'2' + 26,
+            _ => return None,
+        };
+        bits = (bits << 5) | u64::from(value);
+        bit_count += 5;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            hash[i] = (bits >> bit_count) as u8;
+            i += 1;
+        }
+    }
+    Some(hash)
+}
+
+/// Gets the info hash out of an `xt` value if it is a BitTorrent one
+fn hash_from_urn(urn: &str) -> Option<[u8; 20]> {
+    let prefix = urn.get(..9)?;
+    if !prefix.eq_ignore_ascii_case("urn:btih:") {
+        return None;
+    }
+    let hash = &urn[9..];
+    hash_from_hex(hash).or_else(|| hash_from_base32(hash))
+}
+
+/// Parses a `so` value like `0,2,4-6`
+///
+/// Fails if it selects more than `MAX_SELECT_ONLY` files.
+fn parse_select_only(value: &str) -> TrResult<Vec<u32>> {
+    let mut indices = Vec::new();
+    for part in value.split(',') {
+        let mut range = part.splitn(2, '-');
+        let first: u32 = range
+            .next()
+            .unwrap_or("")
+            .parse()
+            .map_err(|_| Error::MagnetInvalid)?;
+        let last = match range.next() {
+            Some(last) => last.parse().map_err(|_| Error::MagnetInvalid)?,
+            None => first,
+        };
+        if last < first || (last - first) as usize >= MAX_SELECT_ONLY - indices.len() {
+            return Err(Error::MagnetInvalid);
+        }
+        indices.extend(first..=last);
+    }
+    Ok(indices)
+}
+
+/// Formats file indices as a `so` value, joining runs into ranges
+fn format_select_only(indices: &[u32]) -> String {
+    let mut parts: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < indices.len() {
+        let first = indices[i];
+        let mut last = first;
+        while i + 1 < indices.len() && indices[i + 1] == last + 1 {
+            last += 1;
+            i += 1;
+        }
+        if first == last {
+            parts.push(first.to_string());
+        } else {
+            parts.push(format!("{}-{}", first, last));
+        }
+        i += 1;
+    }
+    parts.join(",")
+}
+
+/// Percent encodes everything but unreserved URI characters
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Decodes a percent encoded query value, where `+` is a space
+fn percent_decode(s: &str) -> TrResult<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = s.get(i + 1..i + 3).ok_or(Error::MagnetInvalid)?;
+                decoded.push(hex_byte(hex).ok_or(Error::MagnetInvalid)?);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            b => {
+                decoded.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| Error::MagnetInvalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_hash() {
+        let hash = hash_from_hex("f04905751c91af11a3745b1ce4500f4bf0de0d18").unwrap();
+        assert_eq!(hash[0], 0xf0);
+        assert_eq!(hash[19], 0x18);
+        assert!(hash_from_hex("f04905751c91af11a3745b1ce4500f4bf0de0d1").is_none());
+        assert!(hash_from_hex("z04905751c91af11a3745b1ce4500f4bf0de0d18").is_none());
+        assert!(hash_from_hex("+f4905751c91af11a3745b1ce4500f4bf0de0d18").is_none());
+    }
+
+    #[test]
+    fn base32_hash() {
+        let hex = hash_from_hex("f04905751c91af11a3745b1ce4500f4bf0de0d18").unwrap();
+        let base32 = hash_from_base32("6BEQK5I4SGXRDI3ULMOOIUAPJPYN4DIY").unwrap();
+        assert_eq!(hex, base32);
+        assert!(hash_from_base32("6BEQK5I4SGXRDI3ULMOOIUAPJPYN4DI1").is_none());
+    }
+
+    #[test]
+    fn round_trip() {
+        let uri = "magnet:?xt=urn:btih:f04905751c91af11a3745b1ce4500f4bf0de0d18\
+                   &dn=Some%20Name&xl=1024&tr=udp%3A%2F%2Ftracker.example%3A1337\
+                   &tr=http%3A%2F%2Fother.example%2Fannounce&ws=http%3A%2F%2Fseed.example%2F\
+                   &so=0,2-4";
+        let link = MagnetLink::parse(uri).unwrap();
+        assert_eq!(link.name, Some(String::from("Some Name")));
+        assert_eq!(link.length, Some(1024));
+        assert_eq!(link.trackers.len(), 2);
+        assert_eq!(link.webseeds, vec![String::from("http://seed.example/")]);
+        assert_eq!(link.select_only, vec![0, 2, 3, 4]);
+        assert_eq!(link.to_string(), uri);
+        assert_eq!(MagnetLink::parse(&link.to_string()).unwrap(), link);
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(MagnetLink::parse("http://example.com").is_err());
+        assert!(MagnetLink::parse("magnet:?dn=No+Hash").is_err());
+        assert!(MagnetLink::parse("magnet:?xt=urn:btih:1234").is_err());
+        assert!(MagnetLink::parse(
+            "magnet:?xt=urn:btih:f04905751c91af11a3745b1ce4500f4bf0de0d18&xl=x"
+        )
+        .is_err());
+        assert!(MagnetLink::parse(
+            "magnet:?xt=urn:btih:f04905751c91af11a3745b1ce4500f4bf0de0d18&dn=%+1"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn select_only_limit() {
+        assert_eq!(parse_select_only("0-99999").unwrap().len(), MAX_SELECT_ONLY);
+        assert!(parse_select_only("0-4294967295").is_err());
+        assert!(parse_select_only("0-49999,50000-100000").is_err());
+        assert!(parse_select_only("4294967295").is_ok());
+    }
+}
@@ -1,9 +1,22 @@
+#[cfg(feature = "async")]
+mod asynctorrent;
+mod datamove;
+mod events;
+mod magnetlink;
 pub mod torrent;
 mod torrentbuilder;
 pub mod torrentinfo;
 pub mod torrentstats;
 
-pub use torrent::{Torrent, Priority};
+pub use datamove::{DataMove, MoveState};
+pub use events::Completeness;
+pub(crate) use events::{forget as forget_events, hold as hold_torrents, unlisten, TorrentEvent};
+pub(crate) use magnetlink::hash_from_hex;
+pub use magnetlink::MagnetLink;
+pub use torrent::{AddOutcome, IdleMode, Priority, RatioMode, Torrent};
 pub use torrentbuilder::TorrentBuilder;
-pub use torrentinfo::TorrentInfo;
-pub use torrentstats::{TorrentState, TorrentStats};
+pub use torrentinfo::{TorrentInfo, TrackerTier};
+pub use torrentstats::{
+    FileStats, PeerInfo, PeerSource, TorrentState, TorrentStats, TrackerState, TrackerStats,
+    VerifyResult,
+};
@@ -1,11 +1,14 @@
 //! Various structs containing Torrent information.
 use std::ffi;
+use std::mem;
+use std::ptr::null;
 
 use chrono::prelude::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use transmission_sys;
 
-use super::Priority;
+use super::{MagnetLink, Priority};
+use crate::error::{Error, TrResult};
 
 /// A file that is part of a torrent.
 #[derive(Debug, Serialize, Deserialize)]
@@ -85,6 +88,24 @@ impl From<transmission_sys::tr_tracker_info> for TrackerInfo {
     }
 }
 
+/// A tier of trackers.
+///
+/// Transmission announces to one tracker per tier and keeps the others as backups.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrackerTier {
+    /// The announce URLs of the trackers in the tier
+    pub announce: Vec<String>,
+}
+
+impl TrackerTier {
+    /// Create a new tier from announce URLs
+    pub fn new(announce: Vec<&str>) -> Self {
+        Self {
+            announce: announce.iter().map(|s| String::from(*s)).collect(),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TorrentInfo {
     /// Total download size in bytes
@@ -211,3 +232,74 @@ impl From<transmission_sys::tr_info> for TorrentInfo {
         }
     }
 }
+
+impl TorrentInfo {
+    /// Parse the metainfo of a .torrent file already in memory
+    ///
+    /// Fails with `Error::Bencode` if the bytes are not valid bencode
+    /// and `Error::ParseErr` if they are not a valid torrent.
+    pub fn parse_bytes(bytes: &[u8]) -> TrResult<Self> {
+        unsafe {
+            let ctor = transmission_sys::tr_ctorNew(null());
+            let result =
+                match transmission_sys::tr_ctorSetMetainfo(ctor, bytes.as_ptr(), bytes.len()) {
+                    0 => {
+                        let mut info: transmission_sys::tr_info = mem::zeroed();
+                        match transmission_sys::tr_torrentParse(ctor, &mut info) {
+                            transmission_sys::tr_parse_result::TR_PARSE_OK => {
+                                let parsed = Self::from(info);
+                                transmission_sys::tr_metainfoFree(&mut info);
+                                Ok(parsed)
+                            }
+                            _ => Err(Error::ParseErr),
+                        }
+                    }
+                    _ => Err(Error::Bencode),
+                };
+            transmission_sys::tr_ctorFree(ctor);
+            result
+        }
+    }
+
+    /// Create a magnet link for the torrent
+    ///
+    /// The link has the info hash, display name, webseeds and,
+    /// if `include_trackers` is true, the trackers.
+    /// Private torrents usually should not share their trackers.
+    pub fn to_magnet(&self, include_trackers: bool) -> String {
+        let mut link = MagnetLink::new(self.hash);
+        if !self.name.is_empty() {
+            link.name = Some(self.name.clone());
+        }
+        if include_trackers {
+            link.trackers = self.trackers.iter().map(|t| t.announce.clone()).collect();
+        }
+        link.webseeds = self.webseeds.clone();
+        link.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_bytes() {
+        let info = TorrentInfo::parse_bytes(&std::fs::read("alpine.torrent").unwrap()).unwrap();
+        assert_eq!(info.name, "alpine-extended-3.8.2-x86_64.iso");
+        assert_eq!(info.hash_string, "f04905751c91af11a3745b1ce4500f4bf0de0d18");
+    }
+
+    #[test]
+    fn parse_invalid_bytes() {
+        assert_eq!(
+            TorrentInfo::parse_bytes(b"not bencode").err(),
+            Some(Error::Bencode)
+        );
+        // Valid bencode that is not a torrent
+        assert_eq!(
+            TorrentInfo::parse_bytes(b"d3:fooi1ee").err(),
+            Some(Error::ParseErr)
+        );
+    }
+}
@@ -1,12 +1,16 @@
-//! Contains the TorrentStats struct.
+//! Contains the TorrentStats struct and the other live stats of a torrent.
+use std::ffi;
+use std::os::raw::c_char;
+
 use chrono::prelude::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use transmission_sys;
 
+use super::Priority;
 use crate::error::Error;
 
 /// The various states that a torrent can be in.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum TorrentState {
     /// The torrent is downloading
     Downloading,
@@ -68,7 +72,8 @@ pub struct TorrentStats {
     /// Percent of the desired download completed.
     /// This differs from [`torrent::TorrentStats::percent_complete`] if the user only wants some of a torrent's files.
     pub percent_done: f32,
-    /// Percent of the seed ratio uploaded. 1 if completed or infinite.
+    /// Percent of the seed ratio uploaded.
+    /// 1 if completed or if the torrent has no ratio limit, see `Torrent::seed_ratio()`.
     pub seed_ratio_percent_done: f32,
     /// The raw upload speed.
     pub raw_upload_speed_kbps: f32,
@@ -79,7 +84,11 @@ pub struct TorrentStats {
     /// The actual piece download speed.
     pub piece_download_speed_kbps: f32,
     /// Estimated time of arrival (completion)
+    /// or when seeding the seconds until the seed ratio is reached.
+    /// -1 if not available and -2 if unknown.
     pub eta: i32,
+    /// When seeding the seconds until the idle limit is reached.
+    /// -1 if not available, such as when the torrent has no idle limit, see `Torrent::seed_idle()`.
     pub eta_idle: i32,
     /// Number of peers connected for this torrent.
     pub peers_connected: i32,
@@ -183,3 +192,294 @@ impl From<*const transmission_sys::tr_stat> for TorrentStats {
         }
     }
 }
+
+/// Where a peer was found.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PeerSource {
+    /// The peer connected to our listening port
+    Incoming,
+    /// Local peer discovery
+    Lpd,
+    /// A tracker
+    Tracker,
+    /// The DHT
+    Dht,
+    /// Peer exchange
+    Pex,
+    /// The resume file of a previous session
+    Resume,
+    /// An LTEP handshake
+    Ltep,
+    /// Unknown source
+    Unknown,
+}
+
+impl From<u8> for PeerSource {
+    fn from(from: u8) -> Self {
+        match i32::from(from) {
+            x if x == transmission_sys::TR_PEER_FROM_INCOMING as i32 => PeerSource::Incoming,
+            x if x == transmission_sys::TR_PEER_FROM_LPD as i32 => PeerSource::Lpd,
+            x if x == transmission_sys::TR_PEER_FROM_TRACKER as i32 => PeerSource::Tracker,
+            x if x == transmission_sys::TR_PEER_FROM_DHT as i32 => PeerSource::Dht,
+            x if x == transmission_sys::TR_PEER_FROM_PEX as i32 => PeerSource::Pex,
+            x if x == transmission_sys::TR_PEER_FROM_RESUME as i32 => PeerSource::Resume,
+            x if x == transmission_sys::TR_PEER_FROM_LTEP as i32 => PeerSource::Ltep,
+            _ => PeerSource::Unknown,
+        }
+    }
+}
+
+/// A peer connected to a torrent.
+///
+/// You can get them with `Torrent::peers()`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeerInfo {
+    /// IP address of the peer.
+    pub address: String,
+    /// Port of the peer.
+    pub port: u16,
+    /// Name of the peer's client software.
+    pub client_name: String,
+    /// How much of the torrent the peer has.
+    pub progress: f32,
+    /// Transmission's short flag string for the peer, such as "DEI".
+    pub flags: String,
+    /// Where the peer was found.
+    pub from: PeerSource,
+    /// Is the connection encrypted?
+    pub is_encrypted: bool,
+    /// Is the connection using uTP?
+    pub is_utp: bool,
+    /// Did the peer connect to us?
+    pub is_incoming: bool,
+    /// Does the peer have the entire torrent?
+    pub is_seed: bool,
+    /// Are we downloading from the peer?
+    pub is_downloading_from: bool,
+    /// Are we uploading to the peer?
+    pub is_uploading_to: bool,
+    /// Are we choking the peer?
+    pub peer_is_choked: bool,
+    /// Is the peer interested in our pieces?
+    pub peer_is_interested: bool,
+    /// Is the peer choking us?
+    pub client_is_choked: bool,
+    /// Are we interested in the peer's pieces?
+    pub client_is_interested: bool,
+    /// Upload speed to the peer.
+    pub upload_speed_kbps: f64,
+    /// Download speed from the peer.
+    pub download_speed_kbps: f64,
+}
+
+impl From<&transmission_sys::tr_peer_stat> for PeerInfo {
+    fn from(peer: &transmission_sys::tr_peer_stat) -> Self {
+        Self {
+            address: string_from_c_array(&peer.addr),
+            port: peer.port,
+            client_name: string_from_c_array(&peer.client),
+            progress: peer.progress,
+            flags: string_from_c_array(&peer.flagStr),
+            from: PeerSource::from(peer.from),
+            is_encrypted: peer.isEncrypted,
+            is_utp: peer.isUTP,
+            is_incoming: peer.isIncoming,
+            is_seed: peer.isSeed,
+            is_downloading_from: peer.isDownloadingFrom,
+            is_uploading_to: peer.isUploadingTo,
+            peer_is_choked: peer.peerIsChoked,
+            peer_is_interested: peer.peerIsInterested,
+            client_is_choked: peer.clientIsChoked,
+            client_is_interested: peer.clientIsInterested,
+            upload_speed_kbps: peer.rateToPeer_KBps,
+            download_speed_kbps: peer.rateToClient_KBps,
+        }
+    }
+}
+
+/// What a tracker is doing for the torrent.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TrackerState {
+    /// Not announcing or scraping, for example because the torrent is stopped
+    Inactive,
+    /// Waiting for the tracker's interval to pass
+    Waiting,
+    /// Waiting for a free slot to announce or scrape
+    Queued,
+    /// Announcing or scraping right now
+    Active,
+}
+
+impl From<transmission_sys::tr_tracker_state> for TrackerState {
+    fn from(state: transmission_sys::tr_tracker_state) -> Self {
+        match state {
+            transmission_sys::tr_tracker_state::TR_TRACKER_INACTIVE => TrackerState::Inactive,
+            transmission_sys::tr_tracker_state::TR_TRACKER_WAITING => TrackerState::Waiting,
+            transmission_sys::tr_tracker_state::TR_TRACKER_QUEUED => TrackerState::Queued,
+            transmission_sys::tr_tracker_state::TR_TRACKER_ACTIVE => TrackerState::Active,
+        }
+    }
+}
+
+/// The announce and scrape status of one of a torrent's trackers.
+///
+/// You can get them with `Torrent::tracker_stats()`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackerStats {
+    /// Unique ID matching `TrackerInfo::id`.
+    pub id: u32,
+    /// Which tier the tracker is in.
+    pub tier: i32,
+    /// Human-readable name of the tracker.
+    pub host: String,
+    /// The full announce URL.
+    pub announce: String,
+    /// The full scrape URL.
+    pub scrape: String,
+    /// Is the tracker a backup for the others in its tier?
+    pub is_backup: bool,
+    /// The state of announcing to the tracker.
+    pub announce_state: TrackerState,
+    /// The state of scraping the tracker.
+    pub scrape_state: TrackerState,
+    /// Has the tracker ever been announced to?
+    /// If not the `last_announce_*` fields are meaningless.
+    pub has_announced: bool,
+    /// When the last announce was sent.
+    pub last_announce_start_time: NaiveDateTime,
+    /// When the last announce completed.
+    pub last_announce_time: NaiveDateTime,
+    /// Did the last announce succeed?
+    pub last_announce_succeeded: bool,
+    /// Did the last announce time out?
+    pub last_announce_timed_out: bool,
+    /// Human-readable result of the last announce.
+    pub last_announce_result: String,
+    /// Number of peers the tracker gave us in the last announce.
+    pub last_announce_peer_count: i32,
+    /// When the next announce will be sent.
+    /// Only meaningful when `announce_state` is `TrackerState::Waiting`.
+    pub next_announce_time: NaiveDateTime,
+    /// Has the tracker ever been scraped?
+    /// If not the `last_scrape_*` fields are meaningless.
+    pub has_scraped: bool,
+    /// When the last scrape was sent.
+    pub last_scrape_start_time: NaiveDateTime,
+    /// When the last scrape completed.
+    pub last_scrape_time: NaiveDateTime,
+    /// Did the last scrape succeed?
+    pub last_scrape_succeeded: bool,
+    /// Did the last scrape time out?
+    pub last_scrape_timed_out: bool,
+    /// Human-readable result of the last scrape.
+    pub last_scrape_result: String,
+    /// When the next scrape will be sent.
+    /// Only meaningful when `scrape_state` is `TrackerState::Waiting`.
+    pub next_scrape_time: NaiveDateTime,
+    /// Number of seeders the tracker knows of, -1 if unknown.
+    pub seeder_count: i32,
+    /// Number of leechers the tracker knows of, -1 if unknown.
+    pub leecher_count: i32,
+    /// Number of completed downloads the tracker knows of, -1 if unknown.
+    pub download_count: i32,
+}
+
+impl From<&transmission_sys::tr_tracker_stat> for TrackerStats {
+    fn from(tracker: &transmission_sys::tr_tracker_stat) -> Self {
+        Self {
+            id: tracker.id,
+            tier: tracker.tier,
+            host: string_from_c_array(&tracker.host),
+            announce: string_from_c_array(&tracker.announce),
+            scrape: string_from_c_array(&tracker.scrape),
+            is_backup: tracker.isBackup,
+            announce_state: TrackerState::from(tracker.announceState),
+            scrape_state: TrackerState::from(tracker.scrapeState),
+            has_announced: tracker.hasAnnounced,
+            last_announce_start_time: NaiveDateTime::from_timestamp(
+                tracker.lastAnnounceStartTime,
+                0,
+            ),
+            last_announce_time: NaiveDateTime::from_timestamp(tracker.lastAnnounceTime, 0),
+            last_announce_succeeded: tracker.lastAnnounceSucceeded,
+            last_announce_timed_out: tracker.lastAnnounceTimedOut,
+            last_announce_result: string_from_c_array(&tracker.lastAnnounceResult),
+            last_announce_peer_count: tracker.lastAnnouncePeerCount,
+            next_announce_time: NaiveDateTime::from_timestamp(tracker.nextAnnounceTime, 0),
+            has_scraped: tracker.hasScraped,
+            last_scrape_start_time: NaiveDateTime::from_timestamp(tracker.lastScrapeStartTime, 0),
+            last_scrape_time: NaiveDateTime::from_timestamp(tracker.lastScrapeTime, 0),
+            last_scrape_succeeded: tracker.lastScrapeSucceeded,
+            last_scrape_timed_out: tracker.lastScrapeTimedOut,
+            last_scrape_result: string_from_c_array(&tracker.lastScrapeResult),
+            next_scrape_time: NaiveDateTime::from_timestamp(tracker.nextScrapeTime, 0),
+            seeder_count: tracker.seederCount,
+            leecher_count: tracker.leecherCount,
+            download_count: tracker.downloadCount,
+        }
+    }
+}
+
+/// The download state of a file in a torrent.
+///
+/// You can get them with `Torrent::file_stats()`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileStats {
+    /// Index of the file in the torrent
+    pub index: u32,
+    /// Name of the file
+    pub name: String,
+    /// The length of the file in bytes
+    pub length: u64,
+    /// Bytes of the file that have been downloaded and checked
+    pub bytes_completed: u64,
+    /// Download progress of the file from 0.0 to 1.0
+    pub progress: f32,
+    /// Will the file be downloaded?
+    pub wanted: bool,
+    /// Download priority of the file
+    pub priority: Priority,
+}
+
+impl FileStats {
+    /// Combines the static file info with its live stats
+    pub(crate) fn new(
+        index: u32,
+        file: &transmission_sys::tr_file,
+        stat: &transmission_sys::tr_file_stat,
+    ) -> Self {
+        Self {
+            index,
+            name: unsafe { ffi::CStr::from_ptr(file.name) }
+                .to_string_lossy()
+                .into_owned(),
+            length: file.length,
+            bytes_completed: stat.bytesCompleted,
+            progress: stat.progress,
+            wanted: file.dnd == 0,
+            priority: Priority::from(file.priority),
+        }
+    }
+}
+
+/// The outcome of verifying a torrent.
+///
+/// Passed to the callback of `Torrent::verify_with()`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VerifyResult {
+    /// Was verifying stopped before it finished, or the torrent removed first?
+    pub aborted: bool,
+    /// Number of pieces that were downloaded but failed the check
+    pub failed_pieces: u32,
+}
+
+/// Copies a nul terminated C string out of a fixed size array
+fn string_from_c_array(array: &[c_char]) -> String {
+    if array.contains(&0) {
+        unsafe { ffi::CStr::from_ptr(array.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    } else {
+        String::new()
+    }
+}